@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use drsplayer::{transcode, AudioFormat, Error};
+
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("convert")
+        .about("Converts audio files (Ogg/Opus, Ogg/Vorbis, MP3, WAV and FLAC) into Ogg/Opus files \
+                that can be played over SRS (mono, 16 kHz, 20ms frames)")
+        .arg(clap::Arg::with_name("INPUT")
+            .help("Sets the audio file or the directory (including its sub-directories) to convert")
+            .required(true)
+            .index(1))
+        .arg(clap::Arg::with_name("OUTPUT")
+            .help("Sets the directory the converted files are written to (keeping the directory \
+                   structure of INPUT)")
+            .required(true)
+            .index(2))
+}
+
+/// Converts all audio files and reports the ones that failed. Returns `false` if at least one file
+/// failed to convert.
+pub fn run(matches: &clap::ArgMatches<'_>) -> Result<bool, Error> {
+    // Calling .unwrap() is safe here because both INPUT and OUTPUT are required
+    let input = Path::new(matches.value_of("INPUT").unwrap());
+    let output = Path::new(matches.value_of("OUTPUT").unwrap());
+
+    // the converted files would end up next to (or even replace) the files they are converted from,
+    // and would be converted again the next time
+    if input.is_dir() && absolute(output)?.starts_with(fs::canonicalize(input)?) {
+        error!(
+            "The output directory {} must not be inside the input directory {}",
+            output.to_string_lossy(),
+            input.to_string_lossy()
+        );
+        return Ok(false);
+    }
+
+    let mut failed = Vec::new();
+    let mut conversions = Vec::new();
+    for src in crate::files(input)? {
+        match AudioFormat::detect(&src) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                debug!("Skipping {} (not an audio file)", src.to_string_lossy());
+                continue;
+            }
+            Err(err) => {
+                failed.push((src, err.to_string()));
+                continue;
+            }
+        }
+
+        let dst = if input.is_dir() {
+            output.join(src.strip_prefix(input).unwrap_or(&src))
+        } else {
+            output.join(src.file_name().unwrap_or_default())
+        }
+        .with_extension("ogg");
+        conversions.push((src, dst));
+    }
+
+    // files that only differ by their extension (e.g. intro.mp3 and intro.wav) would be converted
+    // into the same file, so none of them is converted
+    let mut sources: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for (src, dst) in &conversions {
+        sources.entry(dst).or_default().push(src);
+    }
+    let collisions: HashMap<PathBuf, String> = sources
+        .into_iter()
+        .filter(|(_, srcs)| srcs.len() > 1)
+        .map(|(dst, srcs)| {
+            let srcs: Vec<_> = srcs.iter().map(|src| src.to_string_lossy()).collect();
+            let msg = format!(
+                "Error: {} would be converted into the same file {}",
+                srcs.join(", "),
+                dst.to_string_lossy()
+            );
+            (dst.to_path_buf(), msg)
+        })
+        .collect();
+
+    let mut converted = 0;
+    for (src, dst) in conversions {
+        if let Some(msg) = collisions.get(&dst) {
+            failed.push((src, msg.clone()));
+            continue;
+        }
+        if fs::canonicalize(&src)? == absolute(&dst)? {
+            let msg = "Error: The file would be overwritten by its converted version".to_string();
+            failed.push((src, msg));
+            continue;
+        }
+
+        info!("Converting {} ...", src.to_string_lossy());
+        let result = dst
+            .parent()
+            .map(fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .map_err(Error::from)
+            .and_then(|_| transcode(&src, &dst));
+        match result {
+            Ok(()) => converted += 1,
+            Err(err) => failed.push((src, err.to_string())),
+        }
+    }
+
+    println!(
+        "Converted {} of {} file(s) into {}",
+        converted,
+        converted + failed.len(),
+        output.to_string_lossy()
+    );
+    for (path, err) in &failed {
+        println!("Failed converting {}: {}", path.to_string_lossy(), err);
+    }
+
+    Ok(failed.is_empty())
+}
+
+/// Returns the absolute path of the given path, which does not need to exist (yet).
+fn absolute(path: &Path) -> Result<PathBuf, Error> {
+    if let Ok(path) = fs::canonicalize(path) {
+        return Ok(path);
+    }
+
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent.as_os_str().is_empty() => {
+            Ok(absolute(Path::new("."))?.join(name))
+        }
+        (Some(parent), Some(name)) => Ok(absolute(parent)?.join(name)),
+        _ => Ok(std::env::current_dir()?.join(path)),
+    }
+}
@@ -1,9 +1,25 @@
 #[macro_use]
 extern crate log;
 
+#[cfg(feature = "transcode")]
+mod convert;
+mod probe;
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use drsplayer::{
+    Coalition, Encryption, Error, Frequency, Modulation, Player, Position, Server, StationManager,
+    Version, VoiceTransport,
+};
 
-use drsplayer::{Error, Player, Position};
+/// The id of the station created from `--freq` and PATH.
+const DEFAULT_STATION_ID: &str = "default";
 
 pub fn main() -> Result<(), Error> {
     env_logger::Builder::new()
@@ -11,47 +27,374 @@ pub fn main() -> Result<(), Error> {
         .try_init()
         .unwrap();
 
-    let matches = clap::App::new("dcs-radio-station")
+    let app = clap::App::new("dcs-radio-station")
         .version(env!("CARGO_PKG_VERSION"))
+        .setting(clap::AppSettings::SubcommandsNegateReqs);
+    #[cfg(feature = "transcode")]
+    let app = app.subcommand(convert::subcommand());
+    let matches = app
+        .subcommand(probe::subcommand())
         .arg(clap::Arg::with_name("frequency")
             .short("f")
             .long("freq")
             .default_value("255000000")
-            .help("Sets the SRS frequency (in Hz, e.g. 255000000 for 255MHz)")
+            .help("Sets the SRS frequency (in Hz, e.g. 255000000 for 255MHz); can be provided \
+                   multiple times to broadcast on multiple frequencies simultaneously, each \
+                   optionally followed by its modulation (e.g. 30000000:FM)")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(clap::Arg::with_name("modulation")
+            .short("m")
+            .long("modulation")
+            .default_value("AM")
+            .possible_values(&["AM", "FM"])
+            .case_insensitive(true)
+            .help("Sets the SRS modulation (of frequencies without an explicit modulation)")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("encryption_key")
+            .short("e")
+            .long("encryption-key")
+            .help("Encrypts the broadcast with the given SRS encryption key (1-252)")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("coalition")
+            .short("c")
+            .long("coalition")
+            .default_value("blue")
+            .possible_values(&["blue", "red", "spectator"])
+            .help("Sets the coalition the station broadcasts for")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("host")
+            .long("host")
+            .default_value("127.0.0.1")
+            .help("Sets the SRS server host (IP address or hostname)")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("port")
+            .short("p")
+            .long("port")
+            .default_value("5002")
+            .help("Sets the SRS server port")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("voice_port")
+            .long("voice-port")
+            .help("Sets the SRS server voice port (defaults to the port following the SRS server port \
+                   for TCP, and to the SRS server port for UDP)")
             .takes_value(true))
         .arg(clap::Arg::with_name("loop")
             .short("l")
             .long("loop")
             .help("Enables endlessly looping the audio file(s)"))
+        .arg(clap::Arg::with_name("voice_transport")
+            .long("voice-transport")
+            .default_value("TCP")
+            .possible_values(&["TCP", "UDP"])
+            .case_insensitive(true)
+            .help("Sets the transport used to send voice packets to SRS (newer SRS servers use UDP)")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("srs_version")
+            .long("srs-version")
+            .default_value("1.6.0.0")
+            .help("Sets the SRS client version the station identifies itself with")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("listener_gated")
+            .long("listener-gated")
+            .help("Only sends audio while at least one SRS client is tuned to the frequency"))
+        .arg(clap::Arg::with_name("record")
+            .short("r")
+            .long("record")
+            .help("Records what is received on the frequency into the directory at PATH instead of \
+                   playing"))
+        .arg(clap::Arg::with_name("station")
+            .short("s")
+            .long("station")
+            .help("Adds a station with the given id that plays PATH on FREQ (in Hz, optionally \
+                   followed by its modulation), e.g. atis=251000000:AM=atis/; can be provided \
+                   multiple times to run multiple stations side by side (running stations can be \
+                   listed, stopped and restarted by entering list, stop <id> or start <id>)")
+            .value_name("ID=FREQ=PATH")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
         .arg(clap::Arg::with_name("PATH")
-            .help("Sets the path audio file(s) should be read from")
-            .required(true)
+            .help("Sets the path audio file(s) should be read from (or recordings are written to)")
+            .required_unless("station")
             .index(1))
         .get_matches();
 
-    // Calling .unwrap() is safe here because "INPUT" is required
-    let path = matches.value_of("PATH").unwrap();
+    #[cfg(feature = "transcode")]
+    {
+        if let Some(matches) = matches.subcommand_matches("convert") {
+            if !convert::run(matches)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("probe") {
+        if !probe::run(matches)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let should_loop = matches.is_present("loop");
-    let freq = matches.value_of("frequency").unwrap();
-    let freq = if let Ok(n) = u64::from_str(freq) {
-        n
+    let modulation = match Modulation::from_str(matches.value_of("modulation").unwrap()) {
+        Ok(modulation) => modulation,
+        Err(err) => {
+            error!("{}", err);
+            return Ok(());
+        }
+    };
+
+    let encryption = match matches.value_of("encryption_key").map(u8::from_str) {
+        Some(Ok(key)) => match Encryption::new(key) {
+            Ok(encryption) => Some(encryption),
+            Err(err) => {
+                error!("{}", err);
+                return Ok(());
+            }
+        },
+        Some(Err(_)) => {
+            error!("The provided encryption key is not a number between 1 and 252");
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let mut freqs = Vec::new();
+    for freq in matches.values_of("frequency").unwrap() {
+        match parse_frequency(freq, modulation, encryption) {
+            Ok(freq) => freqs.push(freq),
+            Err(err) => {
+                error!("{}", err);
+                return Ok(());
+            }
+        }
+    }
+
+    let coalition = match Coalition::from_str(matches.value_of("coalition").unwrap()) {
+        Ok(coalition) => coalition,
+        Err(err) => {
+            error!("{}", err);
+            return Ok(());
+        }
+    };
+
+    let host = matches.value_of("host").unwrap();
+    let port = matches.value_of("port").unwrap();
+    let mut server = if let Ok(port) = u16::from_str(port) {
+        Server::new(host, port)
     } else {
-        error!("The provided frequency is not a valid number");
+        error!("The provided port is not a valid port number");
         return Ok(());
     };
+    if let Some(voice_port) = matches.value_of("voice_port") {
+        if let Ok(voice_port) = u16::from_str(voice_port) {
+            server.set_voice_port(voice_port);
+        } else {
+            error!("The provided voice port is not a valid port number");
+            return Ok(());
+        }
+    }
 
-    let player = Player::new(
+    let voice_transport =
+        match VoiceTransport::from_str(matches.value_of("voice_transport").unwrap()) {
+            Ok(voice_transport) => voice_transport,
+            Err(err) => {
+                error!("{}", err);
+                return Ok(());
+            }
+        };
+
+    let version = match Version::from_str(matches.value_of("srs_version").unwrap()) {
+        Ok(version) => version,
+        Err(err) => {
+            error!("{}", err);
+            return Ok(());
+        }
+    };
+
+    let mut player = Player::new(
         "DCS Radio Station",
         Position {
             x: 0.0,
             y: 0.0,
             alt: 8000.0,
         },
-        freq,
+        freqs[0].freq,
     );
+    if let Err(err) = player.set_frequencies(freqs) {
+        error!("{}", err);
+        return Ok(());
+    }
+    player.set_coalition(coalition);
+    player.set_server(server);
+    player.set_voice_transport(voice_transport);
+    player.set_version(version);
+    player.set_listener_gated(matches.is_present("listener_gated"));
+
+    // all stations to run, keyed by their id
+    let mut stations = BTreeMap::new();
+    if let Some(path) = matches.value_of("PATH") {
+        stations.insert(
+            DEFAULT_STATION_ID.to_string(),
+            (player.clone(), PathBuf::from(path)),
+        );
+    }
+    for station in matches.values_of("station").into_iter().flatten() {
+        let mut parts = station.splitn(3, '=');
+        let (id, freq, path) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(id), Some(freq), Some(path)) if !id.is_empty() => (id, freq, path),
+            _ => {
+                error!("The station `{}` is not of the form ID=FREQ=PATH", station);
+                return Ok(());
+            }
+        };
+        let freq = match parse_frequency(freq, modulation, encryption) {
+            Ok(freq) => freq,
+            Err(err) => {
+                error!("{}", err);
+                return Ok(());
+            }
+        };
+        if stations.contains_key(id) {
+            error!("The station id `{}` is used more than once", id);
+            return Ok(());
+        }
+
+        let mut station_player = player.clone();
+        station_player.set_name(id);
+        if let Err(err) = station_player.set_frequencies(vec![freq]) {
+            error!("{}", err);
+            return Ok(());
+        }
+        stations.insert(id.to_string(), (station_player, PathBuf::from(path)));
+    }
+
+    let record = matches.is_present("record");
+    let start = |manager: &mut StationManager, id: &str| -> Result<(), Error> {
+        let (player, path) = match stations.get(id) {
+            Some(station) => station,
+            None => return Err(Error::UnknownStation(id.to_string())),
+        };
+        if record {
+            manager.record(id, player.clone(), path)
+        } else {
+            manager.spawn(id, player.clone(), path, should_loop)
+        }
+    };
+
+    let mut manager = StationManager::new();
+    if record {
+        info!("Start recording ...");
+    } else {
+        info!("Start playing ...");
+    }
+    for id in stations.keys() {
+        start(&mut manager, id)?;
+    }
+
+    // read commands to control the individual stations from stdin
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+
+    while !manager.is_empty() {
+        for id in manager.remove_finished() {
+            info!("Station {} finished", id);
+        }
 
-    info!("Start playing ...");
-    player.start(path, should_loop)?;
+        let line = match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                // stdin got closed, keep running until all stations finished
+                thread::sleep(Duration::from_millis(500));
+                continue;
+            }
+        };
+        let mut args = line.split_whitespace();
+        match (args.next(), args.next()) {
+            (Some("list"), None) => {
+                for (id, handle) in manager.stations() {
+                    let freqs: Vec<String> = handle
+                        .frequencies()
+                        .iter()
+                        .map(|freq| format!("{}:{:?}", freq.freq, freq.modulation))
+                        .collect();
+                    println!("{} ({})", id, freqs.join(", "));
+                }
+            }
+            (Some("stop"), Some(id)) => {
+                if manager.stop(id) {
+                    info!("Stopped station {}", id);
+                } else {
+                    error!("Station {} is not running", id);
+                }
+            }
+            (Some("start"), Some(id)) => {
+                if let Err(err) = start(&mut manager, id) {
+                    error!("{}", err);
+                }
+            }
+            (None, _) => {}
+            _ => error!("Unknown command `{}` (expected list, stop <id> or start <id>)", line),
+        }
+    }
 
     Ok(())
 }
+
+/// Parses a frequency (in Hz), optionally followed by its modulation (e.g. 30000000:FM).
+fn parse_frequency(
+    freq: &str,
+    modulation: Modulation,
+    encryption: Option<Encryption>,
+) -> Result<Frequency, String> {
+    let mut parts = freq.splitn(2, ':');
+    let freq = match u64::from_str(parts.next().unwrap()) {
+        Ok(freq) => freq,
+        Err(_) => return Err("The provided frequency is not a valid number".to_string()),
+    };
+    let modulation = match parts.next().map(Modulation::from_str) {
+        Some(Ok(modulation)) => modulation,
+        Some(Err(err)) => return Err(err.to_string()),
+        None => modulation,
+    };
+    let mut freq = Frequency::new(freq, modulation);
+    freq.encryption = encryption;
+
+    Ok(freq)
+}
+
+/// Returns the given file, or all files inside the given directory and its sub-directories (in
+/// order).
+fn files(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut entries = path
+        .read_dir()?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for entry in entries {
+        files.extend(self::files(&entry)?);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::time::Duration;
+
+use drsplayer::{probe, AudioFormat, AudioInfo, Error};
+
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("probe")
+        .about("Reports the properties of audio files and checks whether they can be played over \
+                SRS, after transcoding them if necessary (exits with a non-zero exit code if not)")
+        .arg(clap::Arg::with_name("PATH")
+            .help("Sets the audio file(s) or directories (including their sub-directories) to probe")
+            .required(true)
+            .multiple(true)
+            .index(1))
+}
+
+/// Probes all audio files and prints a report for each of them. Returns `false` if at least one
+/// file cannot be played correctly over SRS, even after transcoding it.
+pub fn run(matches: &clap::ArgMatches<'_>) -> Result<bool, Error> {
+    let mut ok = true;
+
+    // Calling .unwrap() is safe here because PATH is required
+    for path in matches.values_of("PATH").unwrap() {
+        let path = Path::new(path);
+        for file in crate::files(path)? {
+            // inside directories, silently skip everything that is not an audio file
+            if path.is_dir() {
+                if let Ok(None) = AudioFormat::detect(&file) {
+                    continue;
+                }
+            }
+
+            println!("{}", file.to_string_lossy());
+            match probe(&file) {
+                Ok(info) => {
+                    print_info(&info);
+                    if !info.is_playable() {
+                        ok = false;
+                    }
+                }
+                Err(err) => {
+                    println!("  PROBLEM: {}", err);
+                    ok = false;
+                }
+            }
+            println!();
+        }
+    }
+
+    Ok(ok)
+}
+
+fn print_info(info: &AudioInfo) {
+    println!("  Container:      {}", info.format.container());
+    println!("  Codec:          {}", info.format.codec());
+    println!("  Channels:       {}", info.channels);
+    println!("  Sample rate:    {} Hz", info.sample_rate);
+    if let Some(bitrate) = info.bitrate {
+        println!("  Bitrate:        {:.1} kb/s", f64::from(bitrate) / 1000.0);
+    }
+    if !info.frame_durations.is_empty() {
+        let durations: Vec<String> = info
+            .frame_durations
+            .iter()
+            .map(|d| format!("{}ms", d.as_micros() as f64 / 1000.0))
+            .collect();
+        println!("  Frame duration: {}", durations.join(", "));
+    }
+    if let Some(duration) = info.duration {
+        println!("  Duration:       {}", format_duration(duration));
+    }
+    if let Some(ref vendor) = info.vendor {
+        println!("  Vendor:         {}", vendor);
+    }
+    for tag in &info.tags {
+        println!("  Tag:            {}", tag);
+    }
+
+    for problem in &info.problems {
+        if problem.is_fixed_by_transcoding() {
+            println!("  Note:           {}", problem);
+        } else {
+            println!("  PROBLEM: {}", problem);
+        }
+    }
+    if info.is_playable() {
+        if !info.needs_transcoding() {
+            println!("  OK");
+        } else if info.format == AudioFormat::Opus {
+            println!("  OK (transcoded to a compatible Opus stream before playing)");
+        } else {
+            println!("  OK (transcoded to Opus before playing)");
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{}:{:02}.{:03}",
+        secs / 60,
+        secs % 60,
+        duration.subsec_millis()
+    )
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "transcode")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("drs-test-{}-{}", std::process::id(), name))
+}
+
+fn convert(input: &Path, output: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dcs-radio-station"))
+        .arg("convert")
+        .arg(input)
+        .arg(output)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn colliding_destinations() {
+    let input = temp_path("colliding_destinations");
+    let output = temp_path("colliding_destinations_out");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("intro.mp3"), b"not converted").unwrap();
+    fs::write(input.join("intro.wav"), b"not converted").unwrap();
+
+    let result = convert(&input, &output);
+    assert!(!result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("Converted 0 of 2 file(s)"), "{}", stdout);
+    for name in &["intro.mp3", "intro.wav"] {
+        let line = format!("Failed converting {}", input.join(name).to_string_lossy());
+        assert!(stdout.contains(&line), "{}", stdout);
+    }
+    assert!(stdout.contains("would be converted into the same file"), "{}", stdout);
+    assert!(!output.join("intro.ogg").exists());
+
+    fs::remove_dir_all(input).unwrap();
+    let _ = fs::remove_dir_all(output);
+}
+
+#[test]
+fn output_inside_input() {
+    let input = temp_path("output_inside_input");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("intro.wav"), b"not converted").unwrap();
+
+    for output in &[input.join("converted"), input.clone()] {
+        let result = convert(&input, output);
+        assert!(!result.status.success());
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(stderr.contains("must not be inside the input directory"), "{}", stderr);
+    }
+    assert!(!input.join("converted").exists());
+
+    fs::remove_dir_all(input).unwrap();
+}
+
+#[test]
+fn overwrite_input_file() {
+    let dir = temp_path("overwrite_input_file");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("intro.ogg");
+    let content = b"OggS OpusHead (not converted)".to_vec();
+    fs::write(&input, &content).unwrap();
+
+    let result = convert(&input, &dir);
+    assert!(!result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("would be overwritten"), "{}", stdout);
+    assert_eq!(fs::read(&input).unwrap(), content);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -9,16 +9,42 @@ extern crate const_cstr;
 #[macro_use]
 mod macros;
 
+use std::collections::BTreeMap;
 use std::ffi::CString;
+use std::path::PathBuf;
 use std::ptr;
 
-use drsplayer::{Error, Player, Position};
+use drsplayer::{Error, Player, Server, StationManager};
 use hlua51::{Lua, LuaFunction, LuaTable};
 use libc::c_int;
 use lua51_sys as ffi;
 
 static mut INITIALIZED: bool = false;
-static mut PLAYER: Option<Player> = None;
+static mut STATIONS: Option<Stations> = None;
+
+struct Stations {
+    manager: StationManager,
+    /// All stations found in the mission (keyed by their id, which is the name of their static or
+    /// unit), to be able to (re)start them individually.
+    mission: BTreeMap<String, Station>,
+}
+
+struct Station {
+    player: Player,
+    path: PathBuf,
+}
+
+impl Stations {
+    fn start(&mut self, id: &str) -> Result<(), Error> {
+        match self.mission.get(id) {
+            Some(station) => {
+                self.manager
+                    .spawn(id, station.player.clone(), &station.path, true)
+            }
+            None => Err(Error::UnknownStation(id.to_string())),
+        }
+    }
+}
 
 pub fn init(lua: &mut Lua<'_>) -> Result<(), Error> {
     unsafe {
@@ -66,8 +92,12 @@ pub fn init(lua: &mut Lua<'_>) -> Result<(), Error> {
 #[no_mangle]
 pub extern "C" fn start(state: *mut ffi::lua_State) -> c_int {
     unsafe {
-        if PLAYER.is_none() {
+        if STATIONS.is_none() {
+            // arguments: path, [host, [port]]; the playlists of the stations are relative to path
+            let argc = ffi::lua_gettop(state);
             let mut lua = Lua::from_existing_state(state, false);
+            let port: Option<u16> = if argc >= 3 { lua.pop() } else { None };
+            let host: Option<String> = if argc >= 2 { lua.pop() } else { None };
             let path: String = match lua.pop() {
                 Some(p) => p,
                 None => {
@@ -84,14 +114,47 @@ pub extern "C" fn start(state: *mut ffi::lua_State) -> c_int {
                 env!("CARGO_PKG_VERSION")
             );
 
-            match Player::create(lua) {
-                Ok(player) => {
-                    PLAYER = Some(player);
-                }
+            let mission_stations = match Player::create(lua) {
+                Ok(mission_stations) => mission_stations,
                 Err(err) => {
                     return report_error(state, &err.to_string());
                 }
+            };
+
+            let mut stations = Stations {
+                manager: StationManager::new(),
+                mission: BTreeMap::new(),
+            };
+            for mut station in mission_stations {
+                if host.is_some() || port.is_some() {
+                    station.player.set_server(Server::new(
+                        host.as_ref().map(|s| s.as_str()).unwrap_or("127.0.0.1"),
+                        port.unwrap_or(5002),
+                    ));
+                }
+
+                let path = match station.path {
+                    Some(ref p) => PathBuf::from(&path).join(p),
+                    None => PathBuf::from(&path),
+                };
+                stations.mission.insert(
+                    station.id,
+                    Station {
+                        player: station.player,
+                        path,
+                    },
+                );
             }
+
+            for id in stations.mission.keys().cloned().collect::<Vec<_>>() {
+                if let Err(err) = stations.start(&id) {
+                    stations.manager.stop_all();
+                    return report_error(state, &err.to_string());
+                }
+            }
+
+            info!("Started {} station(s)", stations.manager.len());
+            STATIONS = Some(stations);
         }
     }
 
@@ -101,9 +164,9 @@ pub extern "C" fn start(state: *mut ffi::lua_State) -> c_int {
 #[no_mangle]
 pub extern "C" fn stop(_state: *mut ffi::lua_State) -> c_int {
     unsafe {
-        if let Some(player) = PLAYER.take() {
+        if let Some(mut stations) = STATIONS.take() {
             info!("Stopping ...");
-            player.stop()
+            stations.manager.stop_all();
         }
     }
 
@@ -113,9 +176,9 @@ pub extern "C" fn stop(_state: *mut ffi::lua_State) -> c_int {
 #[no_mangle]
 pub extern "C" fn pause(_state: *mut ffi::lua_State) -> c_int {
     unsafe {
-        if let Some(ref mut player) = PLAYER {
+        if let Some(ref stations) = STATIONS {
             debug!("Pausing ...");
-            player.pause()
+            stations.manager.pause_all();
         }
     }
 
@@ -125,15 +188,110 @@ pub extern "C" fn pause(_state: *mut ffi::lua_State) -> c_int {
 #[no_mangle]
 pub extern "C" fn unpause(_state: *mut ffi::lua_State) -> c_int {
     unsafe {
-        if let Some(ref mut player) = PLAYER {
+        if let Some(ref stations) = STATIONS {
             debug!("Unpausing ...");
-            player.unpause()
+            stations.manager.unpause_all();
         }
     }
 
     0
 }
 
+/// Re-reads the positions of the units stations are bound to (if any) and forwards them to SRS.
+/// This is expected to be called regularly from a hook (e.g. every few seconds from
+/// `onSimulationFrame`).
+#[no_mangle]
+pub extern "C" fn update(state: *mut ffi::lua_State) -> c_int {
+    unsafe {
+        if let Some(ref stations) = STATIONS {
+            for (id, handle) in stations.manager.stations() {
+                let unit = match stations.mission.get(id).and_then(|s| s.player.unit()) {
+                    Some(unit) => unit,
+                    None => continue,
+                };
+                let lua = Lua::from_existing_state(state, false);
+                match drsplayer::read_unit_position(lua, unit) {
+                    Ok(Some((position, lat_lng_position))) => {
+                        handle.set_position(position);
+                        handle.set_lat_lng_position(lat_lng_position);
+                    }
+                    Ok(None) => debug!("Unit {} does not exist (anymore)", unit),
+                    Err(err) => {
+                        return report_error(state, &err.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    0
+}
+
+/// (Re)starts the mission station with the given id (the name of its static or unit).
+#[no_mangle]
+pub extern "C" fn start_station(state: *mut ffi::lua_State) -> c_int {
+    unsafe {
+        if let Some(ref mut stations) = STATIONS {
+            let mut lua = Lua::from_existing_state(state, false);
+            let id: String = match lua.pop() {
+                Some(id) => id,
+                None => {
+                    return report_error(state, "station id argument required");
+                }
+            };
+
+            if let Err(err) = stations.start(&id) {
+                return report_error(state, &err.to_string());
+            }
+        }
+    }
+
+    0
+}
+
+/// Stops the station with the given id (the name of its static or unit).
+#[no_mangle]
+pub extern "C" fn stop_station(state: *mut ffi::lua_State) -> c_int {
+    unsafe {
+        if let Some(ref mut stations) = STATIONS {
+            let mut lua = Lua::from_existing_state(state, false);
+            let id: String = match lua.pop() {
+                Some(id) => id,
+                None => {
+                    return report_error(state, "station id argument required");
+                }
+            };
+
+            if stations.manager.stop(&id) {
+                info!("Stopped station {}", id);
+            } else {
+                debug!("Station {} is not running", id);
+            }
+        }
+    }
+
+    0
+}
+
+/// Returns a table with the ids of all running stations.
+#[no_mangle]
+pub extern "C" fn list(state: *mut ffi::lua_State) -> c_int {
+    unsafe {
+        ffi::lua_createtable(state, 0, 0);
+
+        if let Some(ref mut stations) = STATIONS {
+            stations.manager.remove_finished();
+            for (i, id) in stations.manager.ids().into_iter().enumerate() {
+                let id = CString::new(id).unwrap();
+                ffi::lua_pushstring(state, id.as_ptr());
+                ffi::lua_rawseti(state, -2, i as c_int + 1);
+            }
+        }
+    }
+
+    1
+}
+
 fn report_error(state: *mut ffi::lua_State, msg: &str) -> c_int {
     let msg = CString::new(msg).unwrap();
 
@@ -165,6 +323,22 @@ pub unsafe extern "C" fn luaopen_drs(state: *mut ffi::lua_State) -> c_int {
             name: cstr!("unpause"),
             func: Some(unpause),
         },
+        ffi::luaL_Reg {
+            name: cstr!("update"),
+            func: Some(update),
+        },
+        ffi::luaL_Reg {
+            name: cstr!("start_station"),
+            func: Some(start_station),
+        },
+        ffi::luaL_Reg {
+            name: cstr!("stop_station"),
+            func: Some(stop_station),
+        },
+        ffi::luaL_Reg {
+            name: cstr!("list"),
+            func: Some(list),
+        },
         ffi::luaL_Reg {
             name: ptr::null(),
             func: None,
@@ -12,11 +12,44 @@ pub enum Error {
     Undefined(String),
     Tcp(std::io::Error),
     Json(serde_json::error::Error),
+    Protocol(drs_protocol::Error),
     Request(reqwest::Error),
     Base64Decode(base64::DecodeError),
     Ogg(ogg::reading::OggReadError),
     OggMetadata(ogg_metadata::OggMetadataError),
+    #[cfg(feature = "transcode")]
+    Mp3(minimp3::Error),
+    #[cfg(feature = "transcode")]
+    Wav(hound::Error),
+    #[cfg(feature = "transcode")]
+    Flac(claxon::Error),
+    #[cfg(feature = "transcode")]
+    Vorbis(lewton::VorbisError),
+    #[cfg(feature = "transcode")]
+    Opus(audiopus::Error),
+    /// The audio file is neither Ogg/Opus nor one of the formats that can be transcoded into it.
+    UnsupportedFormat(std::path::PathBuf),
+    /// The audio file needs to be transcoded to be played, but the crate was built without the
+    /// `transcode` feature.
+    TranscodingDisabled(std::path::PathBuf),
     NoStationFound,
+    InvalidVersion(String),
+    InvalidVoiceTransport(String),
+    /// The voice port must be set explicitly for TCP if the SRS server port (the given port) is
+    /// 65535, since the default voice port is the port following it.
+    NoVoicePort(u16),
+    /// SRS encryption keys range from 1 to 252.
+    InvalidEncryptionKey(u8),
+    /// The SRS server (second version) is not compatible with the player's client version (first
+    /// version).
+    IncompatibleServerVersion(crate::Version, crate::Version),
+    NoSyncReply,
+    /// A player must broadcast on at least one frequency.
+    NoFrequency,
+    /// A station with the given id is already running.
+    StationExists(String),
+    /// There is no station with the given id.
+    UnknownStation(String),
 }
 
 impl fmt::Display for Error {
@@ -30,6 +63,46 @@ impl fmt::Display for Error {
                 "Error: Trying to access undefined lua global or table key: {}",
                 key
             )?,
+            InvalidVersion(s) => write!(
+                f,
+                "Error: Invalid version `{}` (expected e.g. 1.6.0.0)",
+                s
+            )?,
+            InvalidVoiceTransport(s) => write!(
+                f,
+                "Error: Invalid voice transport `{}` (expected TCP or UDP)",
+                s
+            )?,
+            NoVoicePort(port) => write!(
+                f,
+                "Error: There is no default TCP voice port for SRS server port {} (set the voice \
+                 port explicitly)",
+                port
+            )?,
+            InvalidEncryptionKey(key) => write!(
+                f,
+                "Error: Invalid encryption key {} (expected a key from 1 to 252)",
+                key
+            )?,
+            IncompatibleServerVersion(client, server) => write!(
+                f,
+                "Error: SRS server version {} is not compatible with client version {}",
+                server, client
+            )?,
+            StationExists(id) => write!(f, "Error: A station with the id `{}` is already running", id)?,
+            UnknownStation(id) => write!(f, "Error: There is no station with the id `{}`", id)?,
+            UnsupportedFormat(path) => write!(
+                f,
+                "Error: Unsupported audio format of {} (expected Ogg/Opus, Ogg/Vorbis, MP3, WAV or \
+                 FLAC)",
+                path.to_string_lossy()
+            )?,
+            TranscodingDisabled(path) => write!(
+                f,
+                "Error: {} needs to be transcoded to be played, which requires the `transcode` \
+                 feature",
+                path.to_string_lossy()
+            )?,
             _ => write!(f, "Error: {}", self.description())?,
         }
 
@@ -53,11 +126,33 @@ impl error::Error for Error {
             Undefined(_) => "Trying to access lua gobal or table key that does not exist",
             Tcp(_) => "Error establishing TCP connection to SRS",
             Json(_) => "Error serializing/deserializing JSON RPC message",
+            Protocol(_) => "Error encoding/decoding SRS message",
             Request(_) => "Error sending TTS request",
             Base64Decode(_) => "Error decoding TTS audio content",
             Ogg(_) => "Error decoding OGG audio stream",
             OggMetadata(_) => "Error reading OGG metadata",
+            #[cfg(feature = "transcode")]
+            Mp3(_) => "Error decoding MP3 audio stream",
+            #[cfg(feature = "transcode")]
+            Wav(_) => "Error decoding WAV audio stream",
+            #[cfg(feature = "transcode")]
+            Flac(_) => "Error decoding FLAC audio stream",
+            #[cfg(feature = "transcode")]
+            Vorbis(_) => "Error decoding Vorbis audio stream",
+            #[cfg(feature = "transcode")]
+            Opus(_) => "Error encoding Opus audio stream",
+            UnsupportedFormat(_) => "Unsupported audio format",
+            TranscodingDisabled(_) => "Transcoding is not enabled",
             NoStationFound => "No SRS station found in mission",
+            InvalidVersion(_) => "Invalid version",
+            InvalidVoiceTransport(_) => "Invalid voice transport",
+            NoVoicePort(_) => "No default voice port",
+            InvalidEncryptionKey(_) => "Invalid encryption key",
+            IncompatibleServerVersion(_, _) => "Incompatible SRS server version",
+            NoSyncReply => "SRS server did not reply to the sync message",
+            NoFrequency => "No frequency to broadcast on",
+            StationExists(_) => "Station already running",
+            UnknownStation(_) => "Unknown station",
         }
     }
 
@@ -68,10 +163,21 @@ impl error::Error for Error {
             Lua(ref err) => Some(err),
             Tcp(ref err) => Some(err),
             Json(ref err) => Some(err),
+            Protocol(ref err) => Some(err),
             Request(ref err) => Some(err),
             Base64Decode(ref err) => Some(err),
             Ogg(ref err) => Some(err),
             OggMetadata(ref err) => Some(err),
+            #[cfg(feature = "transcode")]
+            Mp3(ref err) => Some(err),
+            #[cfg(feature = "transcode")]
+            Wav(ref err) => Some(err),
+            #[cfg(feature = "transcode")]
+            Flac(ref err) => Some(err),
+            #[cfg(feature = "transcode")]
+            Vorbis(ref err) => Some(err),
+            #[cfg(feature = "transcode")]
+            Opus(ref err) => Some(err),
             _ => None,
         }
     }
@@ -101,6 +207,12 @@ impl From<serde_json::error::Error> for Error {
     }
 }
 
+impl From<drs_protocol::Error> for Error {
+    fn from(err: drs_protocol::Error) -> Self {
+        Error::Protocol(err)
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
         Error::Request(err)
@@ -124,3 +236,38 @@ impl From<ogg_metadata::OggMetadataError> for Error {
         Error::OggMetadata(err)
     }
 }
+
+#[cfg(feature = "transcode")]
+impl From<minimp3::Error> for Error {
+    fn from(err: minimp3::Error) -> Self {
+        Error::Mp3(err)
+    }
+}
+
+#[cfg(feature = "transcode")]
+impl From<hound::Error> for Error {
+    fn from(err: hound::Error) -> Self {
+        Error::Wav(err)
+    }
+}
+
+#[cfg(feature = "transcode")]
+impl From<claxon::Error> for Error {
+    fn from(err: claxon::Error) -> Self {
+        Error::Flac(err)
+    }
+}
+
+#[cfg(feature = "transcode")]
+impl From<lewton::VorbisError> for Error {
+    fn from(err: lewton::VorbisError) -> Self {
+        Error::Vorbis(err)
+    }
+}
+
+#[cfg(feature = "transcode")]
+impl From<audiopus::Error> for Error {
+    fn from(err: audiopus::Error) -> Self {
+        Error::Opus(err)
+    }
+}
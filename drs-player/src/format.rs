@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::Error;
+
+/// The audio formats the player can play (Opus) or transcode into Opus (all others).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AudioFormat {
+    Opus,
+    Vorbis,
+    Mp3,
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Detects the format of the given audio file, based on its extension and (for Ogg files) the
+    /// codecs of its logical streams (preferring Opus). Returns `None` for unsupported files.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<Option<AudioFormat>, Error> {
+        let path = path.as_ref();
+        let ext = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => ext.to_lowercase(),
+            None => return Ok(None),
+        };
+
+        Ok(match ext.as_str() {
+            "ogg" | "opus" | "oga" => {
+                let mut head = [0; 1024];
+                let mut file = File::open(path)?;
+                let len = read_up_to(&mut file, &mut head)?;
+                // the first pages of an Ogg file contain the ID headers of all of its (multiplexed)
+                // logical streams
+                let head = &head[..len];
+                if contains(head, b"OpusHead") {
+                    Some(AudioFormat::Opus)
+                } else if contains(head, b"\x01vorbis") {
+                    Some(AudioFormat::Vorbis)
+                } else {
+                    None
+                }
+            }
+            "mp3" => Some(AudioFormat::Mp3),
+            "wav" => Some(AudioFormat::Wav),
+            "flac" => Some(AudioFormat::Flac),
+            _ => None,
+        })
+    }
+}
+
+fn read_up_to<R: Read>(rd: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+    while len < buf.len() {
+        match rd.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
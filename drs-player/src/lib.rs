@@ -2,39 +2,85 @@
 #![warn(rust_2018_idioms)]
 
 #[macro_use]
-extern crate log;
+extern crate lazy_static;
 #[macro_use]
-extern crate serde_derive;
+extern crate log;
 
 #[macro_use]
 mod macros;
 mod error;
+mod format;
+mod manager;
+mod mission;
+mod opus;
+mod probe;
+mod recorder;
+mod state;
+#[cfg(feature = "transcode")]
+mod transcode;
+mod version;
+mod voice;
 mod worker;
 
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Cursor, Write};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
 use std::net::TcpStream;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
-use std::{fmt, thread};
+use std::thread;
 
 pub use crate::error::Error;
+pub use crate::format::AudioFormat;
+pub use crate::manager::StationManager;
+pub use crate::mission::{MissionStation, StationConfig};
+pub use crate::probe::{probe, AudioInfo, Problem};
+pub use crate::state::{ClientRadio, ConnectedClient};
+#[cfg(feature = "transcode")]
+pub use crate::transcode::transcode;
+pub use crate::version::Version;
+pub use crate::voice::VoiceTransport;
+use crate::opus::{OggOpusPacket, OggOpusReader};
+use crate::voice::{VoiceConnection, VoiceSocket};
+use crate::state::{ServerState, FREQ_TOLERANCE};
 use crate::worker::{Context, Worker};
-use byteorder::{LittleEndian, WriteBytesExt};
+pub use drs_protocol::{Coalition, LatLngPosition, Modulation, Position};
+use drs_protocol::{Client, Message, MsgType, Radio, RadioInfo, VoiceFrequency, VoicePacket};
 use hlua51::{Lua, LuaFunction, LuaTable};
-use ogg::reading::PacketReader;
 use uuid::Uuid;
 use ogg_metadata::{OggFormat, AudioMetadata};
 use either::Either;
 
-const MAX_FRAME_LENGTH: usize = 1024;
+const RECONNECT_BACKOFF_MIN_SECS: u64 = 1;
+const RECONNECT_BACKOFF_MAX_SECS: u64 = 30;
+const SYNC_REPLY_TIMEOUT_SECS: u64 = 10;
+const UPDATE_INTERVAL_SECS: u64 = 5;
+/// The altitude (in meters, like all DCS altitudes) that is added to the position sent to SRS to
+/// improve the line of sight of stations on the ground.
+const LOS_ALTITUDE_OFFSET: f64 = 100.0;
 
+#[derive(Clone)]
 pub struct Player {
     sguid: String,
-    worker: Vec<Worker<()>>,
     name: String,
     position: Position,
-    freq: u64,
+    lat_lng_position: Option<LatLngPosition>,
+    freqs: Vec<Frequency>,
+    coalition: Coalition,
+    server: Server,
+    voice_transport: VoiceTransport,
+    version: Version,
+    listener_gated: bool,
+    unit: Option<String>,
+    cache_dir: PathBuf,
+    /// The UDP socket voice packets are sent through if it is shared with other stations (see
+    /// `StationManager`); only used for broadcasting, since the packets received on a shared
+    /// socket are meant for all stations using it.
+    voice_socket: Option<VoiceSocket>,
 }
 
 struct OpusFile {
@@ -45,58 +91,166 @@ struct OpusFile {
 
 impl Player {
     pub fn new(name: &str, position: Position, freq: u64) -> Self {
-        let sguid = Uuid::new_v4();
-        let sguid = base64::encode_config(sguid.as_bytes(), base64::URL_SAFE_NO_PAD);
-        assert_eq!(sguid.len(), 22);
-
         Player {
-            sguid,
-            worker: Vec::new(),
+            sguid: new_guid(),
             name: name.to_string(),
             position,
-            freq,
+            lat_lng_position: None,
+            freqs: vec![Frequency::new(freq, Modulation::AM)],
+            coalition: Coalition::Blue,
+            server: Server::default(),
+            voice_transport: VoiceTransport::default(),
+            version: Version::default(),
+            listener_gated: false,
+            unit: None,
+            cache_dir: std::env::temp_dir().join("drs-cache"),
+            voice_socket: None,
         }
     }
 
-    pub fn create(mut lua: Lua<'_>) -> Result<Self, Error> {
+    /// Sets the frequencies the player simultaneously broadcasts on (defaults to the frequency
+    /// provided to `Player::new` with AM modulation). Fails if no frequency is provided.
+    pub fn set_frequencies(&mut self, freqs: Vec<Frequency>) -> Result<(), Error> {
+        if freqs.is_empty() {
+            return Err(Error::NoFrequency);
+        }
+        self.freqs = freqs;
+
+        Ok(())
+    }
+
+    /// Sets the position of the player as latitude and longitude, which is sent in addition to the
+    /// position in DCS coordinates to SRS 1.9 and newer (not set by default). Players created from
+    /// a mission have it set if DCS can convert their position.
+    pub fn set_lat_lng_position(&mut self, lat_lng_position: LatLngPosition) {
+        self.lat_lng_position = Some(lat_lng_position);
+    }
+
+    /// Sets the coalition the player broadcasts for (defaults to blue). If the SRS server enforces
+    /// coalition security, only clients of the same coalition will hear the broadcast.
+    pub fn set_coalition(&mut self, coalition: Coalition) {
+        self.coalition = coalition;
+    }
+
+    /// Sets the SRS server the player connects to (defaults to `127.0.0.1:5002`).
+    pub fn set_server(&mut self, server: Server) {
+        self.server = server;
+    }
+
+    /// Sets the transport used to send voice packets to SRS (defaults to TCP). Newer SRS servers
+    /// expect voice packets to be sent via UDP.
+    pub fn set_voice_transport(&mut self, voice_transport: VoiceTransport) {
+        self.voice_transport = voice_transport;
+    }
+
+    /// Sets the SRS client version the player identifies itself with (defaults to `1.6.0.0`). The
+    /// messages sent follow the format of that version: for SRS 1.9 and newer, the position is not
+    /// sent as part of the radio info anymore, but the latitude and longitude are sent along with
+    /// the client (if set); SRS 2.0 and newer additionally receive the seat and whether the
+    /// broadcast may be recorded. SRS servers reject versions they do not support.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    /// If enabled, voice packets are only sent while at least one client has a radio tuned to the
+    /// player's frequency (disabled by default).
+    pub fn set_listener_gated(&mut self, listener_gated: bool) {
+        self.listener_gated = listener_gated;
+    }
+
+    /// Sets the directory audio files that are not Ogg/Opus (MP3, WAV, FLAC and Ogg/Vorbis) are
+    /// transcoded into before they are played (defaults to `drs-cache` inside the temp directory).
+    /// Outdated versions of changed files are removed from it once they got transcoded again.
+    pub fn set_cache_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.cache_dir = dir.as_ref().to_path_buf();
+    }
+
+    /// Gives the player a new random client GUID. SRS identifies clients by their GUID, so players
+    /// cloned from another player need a GUID of their own to be broadcast side by side (which
+    /// `StationManager` takes care of).
+    pub fn regenerate_guid(&mut self) {
+        self.sguid = new_guid();
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    /// The name of the player (the name of its static or unit for players created from a mission).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the DCS unit the player is bound to, if it was created from a (potentially
+    /// moving) unit instead of a static. Its position can be read using `read_unit_position`.
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_ref().map(|s| s.as_str())
+    }
+
+    /// Creates a player for every static (or unit) in the mission whose name matches the station
+    /// name pattern (see `StationConfig::parse`), e.g. `SRS Player 251.000 AM blue music/`.
+    pub fn create(mut lua: Lua<'_>) -> Result<Vec<MissionStation>, Error> {
         debug!("Extracting ATIS stations from Mission Situation");
 
-        // extract all mission statics to later look for ATIS configs in their names
+        // extract all mission statics and units to later look for ATIS configs in their names
         let mut comm_towers = {
-            // `_current_mission.mission.coalition.{blue,red}.country[i].static.group[j]
+            // `_current_mission.mission.coalition.{blue,red}.country[i].{static,plane,..}.group[j]
             let mut current_mission: LuaTable<_> = get!(lua, "_current_mission")?;
             let mut mission: LuaTable<_> = get!(current_mission, "mission")?;
             let mut coalitions: LuaTable<_> = get!(mission, "coalition")?;
 
             let mut comm_towers = Vec::new();
-            let keys = vec!["blue", "red"];
-            for key in keys {
-                let mut coalition: LuaTable<_> = get!(coalitions, key)?;
+            let keys = vec![
+                ("blue", Coalition::Blue),
+                ("red", Coalition::Red),
+                ("neutrals", Coalition::Spectator),
+            ];
+            for (key, side) in keys {
+                let mut coalition: LuaTable<_> = match coalitions.get(key) {
+                    Some(coalition) => coalition,
+                    // missions created before neutrals were introduced to DCS do not have them
+                    None if side == Coalition::Spectator => continue,
+                    None => return Err(Error::Undefined(key.to_string())),
+                };
                 let mut countries: LuaTable<_> = get!(coalition, "country")?;
 
                 let mut i = 1;
                 while let Some(mut country) = countries.get::<LuaTable<_>, _, _>(i) {
-                    if let Some(mut statics) = country.get::<LuaTable<_>, _, _>("static") {
-                        if let Some(mut groups) = statics.get::<LuaTable<_>, _, _>("group") {
-                            let mut j = 1;
-                            while let Some(mut group) = groups.get::<LuaTable<_>, _, _>(j) {
-                                let x: f64 = get!(group, "x")?;
-                                let y: f64 = get!(group, "y")?;
-
-                                // read `group.units[1].unitId
-                                let mut units: LuaTable<_> = get!(group, "units")?;
-                                let mut first_unit: LuaTable<_> = get!(units, 1)?;
-                                let unit_id: i32 = get!(first_unit, "unitId")?;
-
-                                comm_towers.push(CommTower {
-                                    id: unit_id,
-                                    name: String::new(),
-                                    x,
-                                    y,
-                                    alt: 0.0,
-                                });
-
-                                j += 1;
+                    for category in &["static", "plane", "helicopter", "ship", "vehicle"] {
+                        let is_unit = *category != "static";
+                        if let Some(mut category) = country.get::<LuaTable<_>, _, _>(*category) {
+                            if let Some(mut groups) = category.get::<LuaTable<_>, _, _>("group") {
+                                let mut j = 1;
+                                while let Some(mut group) = groups.get::<LuaTable<_>, _, _>(j) {
+                                    let mut units: LuaTable<_> = get!(group, "units")?;
+
+                                    // statics consist of a single unit, whereas each unit of a
+                                    // group can be a station on its own
+                                    let mut k = 1;
+                                    while let Some(mut unit) = units.get::<LuaTable<_>, _, _>(k) {
+                                        let x: f64 = get!(unit, "x")?;
+                                        let y: f64 = get!(unit, "y")?;
+                                        let unit_id: i32 = get!(unit, "unitId")?;
+
+                                        comm_towers.push(CommTower {
+                                            id: unit_id,
+                                            name: String::new(),
+                                            coalition: side,
+                                            x,
+                                            y,
+                                            alt: 0.0,
+                                            lat_lng: None,
+                                            is_unit,
+                                        });
+
+                                        if !is_unit {
+                                            break;
+                                        }
+                                        k += 1;
+                                    }
+
+                                    j += 1;
+                                }
                             }
                         }
                     }
@@ -106,7 +260,7 @@ impl Player {
             comm_towers
         };
 
-        // extract the names for all statics
+        // extract the names for all statics and units
         {
             // read `DCS.getUnitProperty`
             let mut dcs: LuaTable<_> = get!(lua, "DCS")?;
@@ -128,38 +282,67 @@ impl Player {
             }
         }
 
-        let mut station = comm_towers
+        // convert the positions of all statics and units to latitude and longitude (sent to newer
+        // SRS versions), using `Terrain.convertMetersToLatLon` if available
+        {
+            let mut terrain: LuaTable<_> = get!(lua, "Terrain")?;
+            let convert: Option<LuaFunction<_>> = terrain.get("convertMetersToLatLon");
+            if let Some(mut convert) = convert {
+                for mut tower in &mut comm_towers {
+                    let (lat, lng): (f64, f64) = convert.call_with_args((tower.x, tower.y))?;
+                    tower.lat_lng = Some(LatLngPosition {
+                        lat,
+                        lng,
+                        alt: tower.alt,
+                    });
+                }
+            }
+        }
+
+        let stations: Vec<MissionStation> = comm_towers
             .into_iter()
             .filter_map(|tower| {
-                if tower.name == "SRS Player" {
-                    Some(tower)
-                } else {
-                    None
+                let config = StationConfig::parse(&tower.name)?;
+                debug!("Found station {} ({:?})", tower.name, config);
+
+                let position = Position {
+                    x: tower.x,
+                    y: tower.y,
+                    alt: tower.alt,
+                };
+                let mut player = config.player(position, tower.coalition);
+                player.lat_lng_position = tower.lat_lng;
+                if tower.is_unit {
+                    player.unit = Some(tower.name.clone());
                 }
+
+                Some(MissionStation {
+                    id: tower.name,
+                    player,
+                    path: config.path,
+                })
             })
-            .next();
-
-        if let Some(station) = station.take() {
-            Ok(Player::new(
-                "SRS Radio",
-                Position {
-                    x: station.x,
-                    y: station.y,
-                    alt: station.alt,
-                },
-                255_000_000,
-            ))
-        } else {
+            .collect();
+
+        if stations.is_empty() {
             Err(Error::NoStationFound)
+        } else {
+            Ok(stations)
         }
     }
 
-    pub fn start<P: AsRef<Path>>(mut self, path: P, should_loop: bool) -> Result<(), Error> {
-        if self.worker.len() > 0 {
-            // already started
-            return Ok(());
-        }
+    /// Plays the given audio file (or all audio files inside the given directory) and blocks until
+    /// the playback finished.
+    pub fn start<P: AsRef<Path>>(self, path: P, should_loop: bool) -> Result<(), Error> {
+        let handle = self.spawn(path, should_loop)?;
+        handle.wait();
 
+        Ok(())
+    }
+
+    /// Plays the given audio file (or all audio files inside the given directory) in the
+    /// background and returns a handle to control the playback.
+    pub fn spawn<P: AsRef<Path>>(self, path: P, should_loop: bool) -> Result<PlayerHandle, Error> {
         let file_paths: Vec<PathBuf> = if path.as_ref().is_dir() {
             path.as_ref().read_dir()?.filter_map(|entry| {
                 entry.ok().map(|e| e.path())
@@ -168,225 +351,809 @@ impl Player {
             vec![path.as_ref().into()]
         };
 
-        let mut opus_files = Vec::new();
-        for path in file_paths {
-            if path.extension().is_none() || path.extension().unwrap() != "ogg" {
-                warn!("Ignoring non .ogg file: {:?}", path);
-                continue;
+        let (worker, shared) = self.spawn_sync()?;
+
+        // run audio broadcast
+        let player = self.clone();
+        let broadcast_shared = shared.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let broadcast_running = running.clone();
+        let main = Worker::new(move |ctx| {
+            if let Err(err) =
+                audio_broadcast(ctx, player, &broadcast_shared, file_paths, should_loop)
+            {
+                error!("Error starting SRS broadcast: {}", err);
+            }
+            broadcast_running.store(false, Ordering::SeqCst);
+        });
+
+        Ok(PlayerHandle {
+            main,
+            worker,
+            running,
+            shared,
+        })
+    }
+
+    /// Records everything that is received on the player's frequencies in the background and
+    /// returns a handle to control the recording. Each transmission is written into its own
+    /// Ogg/Opus file inside the given directory (which is created if it does not exist yet), named
+    /// `{timestamp}_{freq}_{sender name}_{sender GUID}.ogg`.
+    pub fn record<P: AsRef<Path>>(self, dir: P) -> Result<PlayerHandle, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (worker, shared) = self.spawn_sync()?;
+
+        let player = self.clone();
+        let record_shared = shared.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let record_running = running.clone();
+        let main = Worker::new(move |ctx| {
+            if let Err(err) = recorder::record(ctx, player, &record_shared, dir) {
+                error!("Error recording SRS transmissions: {}", err);
+            }
+            record_running.store(false, Ordering::SeqCst);
+        });
+
+        Ok(PlayerHandle {
+            main,
+            worker,
+            running,
+            shared,
+        })
+    }
+
+    /// Prepares the given audio file to be broadcast: Opus streams that cannot be sent as they are
+    /// (stereo streams, or packets too large to fit into a voice packet) are transcoded into the
+    /// cache directory just like all other formats. Packets of any valid Opus frame duration are
+    /// sent as they are. Returns `None` (after logging why) if the file cannot be played.
+    fn prepare_file(&self, path: &Path) -> Option<OpusFile> {
+        let format = match AudioFormat::detect(path) {
+            Ok(Some(format)) => format,
+            Ok(None) => {
+                warn!("Ignoring unsupported file: {:?}", path);
+                return None;
             }
-            let mut f = File::open(&path)?;
-            if let Some(OggFormat::Opus(meta)) = ogg_metadata::read_format(&mut f)?.into_iter().next() {
-                if let Some(duration) = meta.get_duration() {
-                    opus_files.push(OpusFile { path, duration });
-                } else {
-                    error!("Failed reading duration of {}", path.to_string_lossy());
+            Err(err) => {
+                error!("Failed reading {}: {}", path.to_string_lossy(), err);
+                return None;
+            }
+        };
+        let needs_transcoding = match format {
+            AudioFormat::Opus => match probe(path) {
+                Ok(info) => info.needs_transcoding(),
+                Err(err) => {
+                    error!("Failed reading {}: {}", path.to_string_lossy(), err);
+                    return None;
                 }
-            } else {
+            },
+            _ => true,
+        };
+        let path = if needs_transcoding {
+            match self.transcoded(path) {
+                Ok(path) => path,
+                Err(err) => {
+                    error!("Failed transcoding {}: {}", path.to_string_lossy(), err);
+                    return None;
+                }
+            }
+        } else {
+            path.to_path_buf()
+        };
+
+        // the Opus stream is not necessarily the first logical stream of the file
+        let formats = File::open(&path)
+            .map_err(Error::from)
+            .and_then(|mut f| Ok(ogg_metadata::read_format(&mut f)?));
+        let meta = match formats {
+            Ok(formats) => formats.into_iter().find_map(|format| match format {
+                OggFormat::Opus(meta) => Some(meta),
+                _ => None,
+            }),
+            Err(err) => {
+                error!("Failed reading {}: {}", path.to_string_lossy(), err);
+                return None;
+            }
+        };
+        match meta.map(|meta| meta.get_duration()) {
+            Some(Some(duration)) => Some(OpusFile { path, duration }),
+            Some(None) => {
+                error!("Failed reading duration of {}", path.to_string_lossy());
+                None
+            }
+            None => {
                 error!("{} is not opus encoded", path.to_string_lossy());
+                None
             }
         }
+    }
 
-        let mut stream = TcpStream::connect("127.0.0.1:5002")?;
-        stream.set_nodelay(true)?;
-        stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    /// Returns the path of the transcoded version of the given audio file inside the cache
+    /// directory, transcoding it first if it is not cached yet.
+    #[cfg(feature = "transcode")]
+    fn transcoded(&self, path: &Path) -> Result<PathBuf, Error> {
+        transcode::cached(path, &self.cache_dir)
+    }
+
+    #[cfg(not(feature = "transcode"))]
+    fn transcoded(&self, path: &Path) -> Result<PathBuf, Error> {
+        Err(Error::TranscodingDisabled(path.into()))
+    }
 
-        let name = format!("ATIS {}", self.name);
-        let sync_msg = Message {
-            client: Some(Client {
-                client_guid: &self.sguid,
-                name: &name,
-                position: self.position.clone(),
-                coalition: Coalition::Blue,
-                radio_info: Some(RadioInfo {
-                    name: "ATIS",
-                    pos: self.position.clone(),
+    /// Connects to SRS and spawns the worker that keeps the sync connection alive.
+    fn spawn_sync(&self) -> Result<(Vec<Worker<()>>, Arc<Shared>), Error> {
+        // fail right away instead of retrying to connect to a voice port that does not exist
+        self.server.voice_addr(self.voice_transport)?;
+
+        let shared = Arc::new(Shared {
+            state: RwLock::new(ServerState::new(&self.sguid)),
+            position: RwLock::new(self.position.clone()),
+            lat_lng_position: RwLock::new(self.lat_lng_position.clone()),
+            freqs: RwLock::new(self.freqs.clone()),
+        });
+        let conn = self.connect_sync(&shared)?;
+        let mut worker = Vec::new();
+
+        // apply the sync reply right away so that the state is already populated once the handle
+        // is returned
+        shared.state.write().unwrap().handle(conn.reply.clone());
+
+        // spawn thread that sends an update RPC call to SRS every ~5 seconds and re-establishes the
+        // connection if it got lost
+        let player = self.clone();
+        let sync_shared = shared.clone();
+        worker.push(Worker::new(move |ctx| {
+            let mut conn = Some(conn);
+            loop {
+                let conn = match conn.take() {
+                    Some(conn) => conn,
+                    None => match reconnect(&ctx, || player.connect_sync(&sync_shared)) {
+                        Some(conn) => conn,
+                        None => return (),
+                    },
+                };
+
+                match player.sync(&ctx, conn, &sync_shared) {
+                    Ok(()) => return (),
+                    Err(err) => error!("Lost sync connection to SRS: {}", err),
+                }
+                sync_shared.state.write().unwrap().clear();
+            }
+        }));
+
+        Ok((worker, shared))
+    }
+
+    /// Returns the frequency (in Hz) out of the given frequencies of the player the given voice
+    /// packet is sent on, if any.
+    fn receives(&self, freqs: &[Frequency], pck: &VoicePacket<'_>) -> Option<u64> {
+        if pck.guid == self.sguid {
+            return None;
+        }
+
+        freqs
+            .iter()
+            .find(|freq| {
+                pck.frequencies.iter().any(|f| {
+                    f.modulation == freq.modulation as u8
+                        && f.encryption == freq.encryption.map(|enc| enc.key).unwrap_or(0)
+                        && (f.freq - freq.freq as f64).abs() < FREQ_TOLERANCE
+                })
+            })
+            .map(|freq| freq.freq)
+    }
+
+    fn client_name(&self) -> String {
+        format!("ATIS {}", self.name)
+    }
+
+    /// Returns the client that is sent to SRS, containing the fields of the player's SRS version.
+    fn client<'a>(
+        &'a self,
+        name: &'a str,
+        position: &Position,
+        lat_lng_position: Option<&LatLngPosition>,
+        radio_info: Option<RadioInfo<'a>>,
+    ) -> Client<'a> {
+        Client {
+            client_guid: Cow::Borrowed(&self.sguid),
+            name: Cow::Borrowed(name),
+            position: los_position(position),
+            lat_lng_position: if self.version.has_lat_lng_position() {
+                lat_lng_position.map(los_lat_lng_position)
+            } else {
+                None
+            },
+            coalition: self.coalition,
+            // stations do not occupy a seat, and are meant to be heard by everyone
+            seat: if self.version.has_seat() { Some(0) } else { None },
+            allow_record: if self.version.has_seat() { Some(true) } else { None },
+            radio_info,
+        }
+    }
+
+    fn sync_message<'a>(
+        &'a self,
+        name: &'a str,
+        position: &Position,
+        lat_lng_position: Option<&LatLngPosition>,
+        freqs: &[Frequency],
+    ) -> Message<'a> {
+        Message {
+            client: Some(self.client(
+                name,
+                position,
+                lat_lng_position,
+                Some(RadioInfo {
+                    name: Cow::Borrowed("ATIS"),
+                    pos: if self.version.has_radio_info_position() {
+                        Some(los_position(position))
+                    } else {
+                        None
+                    },
                     ptt: false,
-                    radios: vec![Radio {
-                        enc: false,
-                        enc_key: 0,
-                        enc_mode: 0, // no encryption
-                        freq_max: 1.0,
-                        freq_min: 1.0,
-                        freq: self.freq as f64,
-                        modulation: 0,
-                        name: "ATIS",
-                        sec_freq: 0.0,
-                        volume: 1.0,
-                        freq_mode: 0, // Cockpit
-                        vol_mode: 0,  // Cockpit
-                        expansion: false,
-                        channel: -1,
-                        simul: false,
-                    }],
+                    radios: freqs
+                        .iter()
+                        .map(|freq| Radio {
+                            enc: freq.encryption.is_some(),
+                            enc_key: freq.encryption.map(|enc| enc.key).unwrap_or(0),
+                            // 0 = no encryption
+                            enc_mode: freq.encryption.map(|enc| enc.mode as u8).unwrap_or(0),
+                            freq_max: 1.0,
+                            freq_min: 1.0,
+                            freq: freq.freq as f64,
+                            modulation: freq.modulation as u8,
+                            name: Cow::Borrowed("ATIS"),
+                            sec_freq: 0.0,
+                            volume: 1.0,
+                            freq_mode: 0, // Cockpit
+                            vol_mode: 0,  // Cockpit
+                            expansion: false,
+                            channel: -1,
+                            simul: false,
+                        })
+                        .collect(),
                     control: 0, // HOTAS
                     selected: 0,
-                    unit: &name,
+                    unit: Cow::Borrowed(name),
                     unit_id: 0,
                     simultaneous_transmission: true,
                 }),
-            }),
+            )),
             msg_type: MsgType::Sync,
-            version: "1.6.0.0",
-        };
+            clients: None,
+            server_settings: None,
+            external_awacs_mode_password: None,
+            version: Cow::Owned(self.version.to_string()),
+        }
+    }
 
-        serde_json::to_writer(&stream, &sync_msg)?;
-        stream.write_all(&['\n' as u8])?;
-
-        let mut rd = BufReader::new(stream.try_clone().unwrap()); // TODO: unwrap?
-
-        // spawn thread that sends an update RPC call to SRS every ~5 seconds
-        let sguid = self.sguid.clone();
-        let mut position = self.position.clone();
-        position.alt += 100.0; // increase sending alt to 100ft above ground for LOS
-        self.worker.push(Worker::new(move |ctx| {
-            let mut send_update = || -> Result<(), Error> {
-                // send update
-                let upd_msg = Message {
-                    client: Some(Client {
-                        client_guid: &sguid,
-                        name: &name,
-                        position: position.clone(),
-                        coalition: Coalition::Blue,
-                        radio_info: None,
-                    }),
-                    msg_type: MsgType::Update,
-                    version: "1.5.6.0",
-                };
+    fn update_message<'a>(
+        &'a self,
+        name: &'a str,
+        position: &Position,
+        lat_lng_position: Option<&LatLngPosition>,
+    ) -> Message<'a> {
+        // the periodic update also keeps the connection to SRS alive
+        Message {
+            client: Some(self.client(name, position, lat_lng_position, None)),
+            msg_type: MsgType::Update,
+            clients: None,
+            server_settings: None,
+            external_awacs_mode_password: None,
+            version: Cow::Owned(self.version.to_string()),
+        }
+    }
 
-                serde_json::to_writer(&mut stream, &upd_msg)?;
-                stream.write_all(&['\n' as u8])?;
+    fn connect_voice(&self) -> Result<VoiceConnection, Error> {
+        VoiceConnection::connect(
+            self.voice_transport,
+            self.server.voice_addr(self.voice_transport)?,
+            &self.sguid,
+        )
+    }
 
-                Ok(())
-            };
+    /// Connects to the SRS voice port to broadcast voice packets, using the shared UDP socket if
+    /// there is one.
+    fn connect_voice_broadcast(&self) -> Result<VoiceConnection, Error> {
+        match (self.voice_transport, &self.voice_socket) {
+            (VoiceTransport::Udp, Some(socket)) => VoiceConnection::shared(socket, &self.sguid),
+            _ => self.connect_voice(),
+        }
+    }
 
-            loop {
-                if let Err(err) = send_update() {
-                    error!("Error sending update to SRS: {}", err);
-                }
+    /// Connects to the SRS sync port, sends the initial sync message and waits for the sync reply
+    /// of the server. SRS servers compare the client version with the minimum version they support
+    /// and reply with a version mismatch message instead if it is older.
+    fn connect_sync(&self, shared: &Shared) -> Result<SyncConnection, Error> {
+        let mut stream = TcpStream::connect(self.server.sync_addr())?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+        let name = self.client_name();
+        let position = shared.position.read().unwrap().clone();
+        let lat_lng_position = shared.lat_lng_position.read().unwrap().clone();
+        let freqs = shared.freqs.read().unwrap().clone();
+        let msg = self.sync_message(&name, &position, lat_lng_position.as_ref(), &freqs);
+        stream.write_all(&msg.encode()?)?;
 
-                //                debug!("SRS Update sent");
+        let mut rd = BufReader::new(stream.try_clone()?);
+        let mut data = Vec::new();
+        let start = Instant::now();
+        loop {
+            match rd.read_until(b'\n', &mut data) {
+                Ok(bytes_read) => {
+                    if bytes_read == 0 {
+                        return Err(Error::Tcp(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "connection closed by SRS",
+                        )));
+                    }
+
+                    // skip messages that cannot be decoded (e.g. of a type unknown to this
+                    // crate) instead of failing to connect at all
+                    let msg = Message::decode(&data);
+                    data.clear();
+                    let msg = match msg {
+                        Ok(msg) => msg,
+                        Err(err) => {
+                            warn!("Error parsing message received from SRS: {}", err);
+                            continue;
+                        }
+                    };
+
+                    match msg.msg_type {
+                        MsgType::Sync => {
+                            match Version::from_str(&msg.version) {
+                                Ok(server_version) => {
+                                    debug!("Connected to SRS server version {}", server_version)
+                                }
+                                Err(_) => warn!("Unknown SRS server version: {}", msg.version),
+                            }
 
-                if ctx.should_stop_timeout(Duration::from_secs(5)) {
-                    return ();
+                            return Ok(SyncConnection {
+                                stream,
+                                rd,
+                                reply: msg,
+                                freqs,
+                            });
+                        }
+                        MsgType::VersionMismatch => {
+                            return Err(Error::IncompatibleServerVersion(
+                                self.version,
+                                Version::from_str(&msg.version)?,
+                            ));
+                        }
+                        // ignore everything else until the sync reply is received
+                        _ => {}
+                    }
                 }
+                Err(err) => match err.kind() {
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                        if start.elapsed() > Duration::from_secs(SYNC_REPLY_TIMEOUT_SECS) {
+                            return Err(Error::NoSyncReply);
+                        }
+                    }
+                    _ => return Err(err.into()),
+                },
             }
-        }));
+        }
+    }
 
-        self.worker.push(Worker::new(move |ctx| {
-            let mut data = Vec::new();
+    /// Sends an update message to SRS every ~5 seconds (and right away whenever the position of the
+    /// player changed), re-syncs whenever the frequencies of the player changed, and reads messages
+    /// received from SRS until either the worker is stopped (`Ok`) or the connection is lost
+    /// (`Err`).
+    fn sync(&self, ctx: &Context, conn: SyncConnection, shared: &Shared) -> Result<(), Error> {
+        let SyncConnection {
+            mut stream,
+            mut rd,
+            reply,
+            freqs: mut synced_freqs,
+        } = conn;
+        shared.state.write().unwrap().handle(reply);
+
+        let name = self.client_name();
+        let mut data = Vec::new();
+        let mut last_update: Option<(Instant, Position, Option<LatLngPosition>)> = None;
+
+        loop {
+            let position = shared.position.read().unwrap().clone();
+            let lat_lng_position = shared.lat_lng_position.read().unwrap().clone();
+            let update_due = last_update
+                .as_ref()
+                .map(|(t, last_position, last_lat_lng_position)| {
+                    t.elapsed() >= Duration::from_secs(UPDATE_INTERVAL_SECS)
+                        || *last_position != position
+                        || *last_lat_lng_position != lat_lng_position
+                })
+                .unwrap_or(true);
+            if update_due {
+                let msg = self.update_message(&name, &position, lat_lng_position.as_ref());
+                stream.write_all(&msg.encode()?)?;
+                last_update = Some((Instant::now(), position.clone(), lat_lng_position.clone()));
+            }
 
-            loop {
-                match rd.read_until(b'\n', &mut data) {
-                    Ok(bytes_read) => {
-                        if bytes_read == 0 {
-                            return ();
-                        }
+            let freqs = shared.freqs.read().unwrap().clone();
+            if freqs != synced_freqs {
+                // SRS learns about the new radio settings through a fresh sync
+                debug!("Frequencies changed, re-syncing with SRS");
+                let msg = self.sync_message(&name, &position, lat_lng_position.as_ref(), &freqs);
+                stream.write_all(&msg.encode()?)?;
+                synced_freqs = freqs;
+            }
 
-                        data.clear();
-                        // ignore received messages ...
+            match rd.read_until(b'\n', &mut data) {
+                Ok(bytes_read) => {
+                    if bytes_read == 0 {
+                        return Err(Error::Tcp(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "connection closed by SRS",
+                        )));
                     }
-                    Err(err) => match err.kind() {
-                        io::ErrorKind::TimedOut => {}
-                        _ => {
-                            error!(
-                                "Error ({:?}) receiving update from SRS: {}",
-                                err.kind(),
-                                err
-                            );
-                        }
-                    },
-                }
 
-                if ctx.should_stop() {
-                    return ();
+                    match Message::decode(&data) {
+                        Ok(msg) => shared.state.write().unwrap().handle(msg),
+                        Err(err) => warn!("Error parsing message received from SRS: {}", err),
+                    }
+                    data.clear();
                 }
+                Err(err) => match err.kind() {
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {}
+                    _ => return Err(err.into()),
+                },
             }
-        }));
 
-        // run audio broadcast
-        let sguid = self.sguid.clone();
-        let freq = self.freq;
-        let broadcast_worker = Worker::new(move |ctx| {
-            if let Err(err) = audio_broadcast(ctx, sguid, freq, opus_files, should_loop) {
-                error!("Error starting SRS broadcast: {}", err);
+            if ctx.should_stop() {
+                return Ok(());
             }
-        });
-        // self.worker.push(broadcast_worker);
-        broadcast_worker.join();
+        }
+    }
+}
 
-        // if we looping, we will never reach this position, if we aren't looping, stop
-        // all other workers since we are done
-        self.stop();
+/// An established sync connection to SRS.
+struct SyncConnection {
+    stream: TcpStream,
+    rd: BufReader<TcpStream>,
+    /// The sync message the server replied with after connecting.
+    reply: Message<'static>,
+    /// The frequencies sent with the sync message.
+    freqs: Vec<Frequency>,
+}
 
-        Ok(())
-    }
+/// The state shared between the workers of a running player and its handle.
+struct Shared {
+    state: RwLock<ServerState>,
+    position: RwLock<Position>,
+    lat_lng_position: RwLock<Option<LatLngPosition>>,
+    freqs: RwLock<Vec<Frequency>>,
+}
 
+/// A handle to a running player, which is returned by `Player::spawn` and `Player::record`.
+pub struct PlayerHandle {
+    /// The worker broadcasting (or recording) the audio.
+    main: Worker<()>,
+    worker: Vec<Worker<()>>,
+    running: Arc<AtomicBool>,
+    shared: Arc<Shared>,
+}
+
+impl PlayerHandle {
+    /// Stops the playback (or recording) and disconnects from SRS.
     pub fn stop(self) {
+        self.main.stop();
         for worker in self.worker.into_iter() {
             worker.stop();
         }
     }
 
     pub fn pause(&self) {
+        self.main.pause();
         for worker in &self.worker {
             worker.pause();
         }
     }
 
     pub fn unpause(&self) {
+        self.main.unpause();
         for worker in &self.worker {
             worker.unpause();
         }
     }
+
+    /// Returns `false` once the playback finished (or the playback or recording failed).
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Moves the running player to the given position, which is sent to SRS right away. Like the
+    /// position the player was created with, it is sent to SRS slightly elevated to improve the line
+    /// of sight of stations on the ground.
+    pub fn set_position(&self, position: Position) {
+        *self.shared.position.write().unwrap() = position;
+    }
+
+    /// Moves the running player to the given latitude and longitude (see
+    /// `Player::set_lat_lng_position`), which is sent to SRS right away.
+    pub fn set_lat_lng_position(&self, lat_lng_position: LatLngPosition) {
+        *self.shared.lat_lng_position.write().unwrap() = Some(lat_lng_position);
+    }
+
+    /// Returns the current position of the player.
+    pub fn position(&self) -> Position {
+        self.shared.position.read().unwrap().clone()
+    }
+
+    /// Retunes the running player to the given frequencies. All voice packets sent afterwards use
+    /// the new frequencies, and SRS is informed about the new radio settings right away. Fails
+    /// (keeping the current frequencies) if no frequency is provided.
+    pub fn set_frequencies(&self, freqs: Vec<Frequency>) -> Result<(), Error> {
+        if freqs.is_empty() {
+            return Err(Error::NoFrequency);
+        }
+        *self.shared.freqs.write().unwrap() = freqs;
+
+        Ok(())
+    }
+
+    /// Returns the frequencies the player currently broadcasts on.
+    pub fn frequencies(&self) -> Vec<Frequency> {
+        self.shared.freqs.read().unwrap().clone()
+    }
+
+    /// Returns all clients currently connected to the SRS server (excluding the player itself).
+    pub fn clients(&self) -> Vec<ConnectedClient> {
+        self.shared.state.read().unwrap().clients()
+    }
+
+    /// Returns the version of the SRS server the player is connected to.
+    pub fn server_version(&self) -> Option<Version> {
+        self.shared.state.read().unwrap().version()
+    }
+
+    /// Returns the settings of the SRS server the player is connected to.
+    pub fn server_settings(&self) -> HashMap<String, String> {
+        self.shared.state.read().unwrap().settings()
+    }
+
+    /// Blocks until the playback finished and disconnects from SRS afterwards. If the player is
+    /// looping or recording, this will block until the playback or recording fails.
+    pub fn wait(self) {
+        self.main.join();
+
+        for worker in self.worker.into_iter() {
+            worker.stop();
+        }
+    }
 }
 
 struct CommTower {
     id: i32,
     name: String,
+    coalition: Coalition,
     x: f64,
     y: f64,
     alt: f64,
+    lat_lng: Option<LatLngPosition>,
+    /// Whether it is a (potentially moving) unit instead of a static
+    is_unit: bool,
+}
+
+fn new_guid() -> String {
+    let sguid = Uuid::new_v4();
+    let sguid = base64::encode_config(sguid.as_bytes(), base64::URL_SAFE_NO_PAD);
+    assert_eq!(sguid.len(), 22);
+    sguid
+}
+
+/// Reads the current position of the DCS unit with the given name (in DCS coordinates and as
+/// latitude and longitude) from the mission scripting environment. Returns `None` if the unit
+/// does not exist (anymore).
+pub fn read_unit_position(
+    mut lua: Lua<'_>,
+    unit: &str,
+) -> Result<Option<(Position, LatLngPosition)>, Error> {
+    // the unit name is handed over as a global and quoted by Lua itself (`%q`) instead of being
+    // spliced into the code, so that no unit name can break out of the string literal; the
+    // result of `dostring_in` is wrapped in parentheses to only return the result string
+    lua.set("drs_unit_name", unit);
+    let result: Result<String, _> = lua.execute(
+        r#"return (net.dostring_in("server", string.format([==[
+            local unit = Unit.getByName(%q)
+            if unit == nil then return "" end
+            local p = unit:getPoint()
+            local lat, lng = coord.LOtoLL(p)
+            return p.x .. "," .. p.z .. "," .. p.y .. "," .. lat .. "," .. lng
+        ]==], drs_unit_name)))"#,
+    );
+    // do not leave the global behind (also if the code failed)
+    lua.execute::<()>("drs_unit_name = nil")?;
+    let result = result?;
+    if result.is_empty() {
+        return Ok(None);
+    }
+
+    // DCS uses the y-axis for the altitude of 3D points
+    let coords = result
+        .split(',')
+        .map(f64::from_str)
+        .collect::<Result<Vec<_>, _>>();
+    match coords.as_ref().map(|c| c.as_slice()) {
+        Ok([x, y, alt, lat, lng]) => Ok(Some((
+            Position {
+                x: *x,
+                y: *y,
+                alt: *alt,
+            },
+            LatLngPosition {
+                lat: *lat,
+                lng: *lng,
+                alt: *alt,
+            },
+        ))),
+        _ => {
+            warn!("Received invalid position for unit {}: {}", unit, result);
+            Ok(None)
+        }
+    }
 }
 
 fn audio_broadcast(
     ctx: Context,
-    sguid: String,
-    freq: u64,
-    files: Vec<OpusFile>,
+    player: Player,
+    shared: &Shared,
+    files: Vec<PathBuf>,
     should_loop: bool,
 ) -> Result<(), Error> {
-    let mut stream = TcpStream::connect("127.0.0.1:5003")?;
-    stream.set_nodelay(true)?;
+    let mut conn = player.connect_voice_broadcast()?;
+    let mut has_listener = true;
 
+    // the files are probed (and transcoded if necessary) right before they are played for the
+    // first time instead of when the player is started, which could take a while for a playlist
+    let mut prepared: HashMap<&Path, Option<OpusFile>> = HashMap::new();
     let iter = if  should_loop {
         Either::Left(files.iter().cycle())
     } else {
         Either::Right(files.iter())
     };
-    for OpusFile { ref path, .. } in iter {
+    for path in iter {
+        let file = prepared
+            .entry(path)
+            .or_insert_with(|| player.prepare_file(path));
+        let path = match file {
+            Some(OpusFile { path, .. }) => path.clone(),
+            None => {
+                // stop looping if none of the files can be played
+                if prepared.len() == files.len() && prepared.values().all(Option::is_none) {
+                    break;
+                }
+                continue;
+            }
+        };
+        if ctx.should_stop() {
+            return Ok(());
+        }
+
         debug!("Playing {}", path.to_string_lossy());
 
         let file = File::open(&path)?;
 
-        let start = Instant::now();
-        let mut size = 0;
-        let mut audio = PacketReader::new(file);
+        // pace the broadcast by the duration of the Opus packets (derived from their TOC byte),
+        // which keeps playback at real-time speed independent of the bitrate (incl. VBR)
+        let mut start = Instant::now();
+        let mut playtime = Duration::from_secs(0);
+        let mut audio = OggOpusReader::new(BufReader::new(file));
         let mut id: u64 = 1;
         while let Some(pck) = audio.read_packet()? {
-            let pck_size = pck.data.len();
-            if pck_size == 0 {
+            let pck = match pck {
+                OggOpusPacket::Audio(pck) => pck,
+                OggOpusPacket::Head(head) => {
+                    debug!("Playing Opus stream ({} channel(s))", head.channels);
+                    continue;
+                }
+                OggOpusPacket::Tags(_) => continue,
+            };
+            if pck.data.is_empty() {
                 continue;
             }
-            size += pck_size;
 
-            let frame = pack_frame(&sguid, id, freq, &pck.data)?;
-            stream.write(&frame)?;
-            id += 1;
+            let duration = match opus::packet_duration(&pck.data) {
+                Some(duration) => duration,
+                None => {
+                    debug!("Skipping packet that is not an opus audio packet");
+                    continue;
+                }
+            };
+            playtime += duration;
+
+            // the frequencies can be changed while playing, work with a snapshot to consistently
+            // send each packet on either the old or the new frequencies
+            let freqs = shared.freqs.read().unwrap().clone();
+
+            // the lengths of voice packets are `u16`s, larger Opus packets cannot be sent at all
+            let max_len = VoicePacket::max_audio_length(freqs.len());
+            if pck.data.len() > max_len {
+                warn!(
+                    "Skipping opus packet of {} bytes (exceeds the maximum of {} bytes)",
+                    pck.data.len(),
+                    max_len
+                );
+                // the pace of the playback is kept, as the duration is already accounted for
+                continue;
+            }
+
+            if player.listener_gated {
+                let had_listener = has_listener;
+                let state = shared.state.read().unwrap();
+                has_listener = freqs
+                    .iter()
+                    .any(|freq| state.has_listener(freq.freq as f64, freq.modulation));
+                if has_listener != had_listener {
+                    if has_listener {
+                        debug!("Client tuned in, resuming sending voice packets");
+                    } else {
+                        debug!("No client tuned in, suspending sending voice packets");
+                    }
+                }
+            }
+
+            // the playback keeps on going while no one is listening, only the voice packets are
+            // not sent (like an actual radio station)
+            if !has_listener {
+                if let Err(err) = conn.keep_alive() {
+                    warn!("Error sending voice keep-alive to SRS: {}", err);
+                }
+
+                let elapsed = Instant::now() - start;
+                if playtime > elapsed {
+                    thread::sleep(playtime - elapsed);
+                }
+
+                if ctx.should_stop() {
+                    return Ok(());
+                }
+                if ctx.was_paused() {
+                    // resume where we left off (without trying to catch up on the time we were
+                    // paused)
+                    start = Instant::now();
+                    playtime = Duration::from_secs(0);
+                }
+
+                continue;
+            }
+
+            let frame = VoicePacket {
+                audio: Cow::Borrowed(&pck.data),
+                frequencies: freqs
+                    .iter()
+                    .map(|freq| VoiceFrequency {
+                        freq: freq.freq as f64,
+                        modulation: freq.modulation as u8,
+                        // the encryption key, or 0 if not encrypted (the encryption mode is only
+                        // part of the radio sent with the sync message)
+                        encryption: freq.encryption.map(|enc| enc.key).unwrap_or(0),
+                    })
+                    .collect(),
+                unit_id: 0,
+                packet_id: id,
+                guid: Cow::Borrowed(&player.sguid),
+            }
+            .encode()?;
+            while let Err(err) = conn.keep_alive().and_then(|_| conn.send(&frame)) {
+                error!("Lost voice connection to SRS: {}", err);
+                conn = match reconnect(&ctx, || player.connect_voice_broadcast()) {
+                    Some(conn) => conn,
+                    None => return Ok(()),
+                };
 
-            // 32 kBit/s
-            let secs = (size * 8) as f64 / 1024.0 / 32.0;
+                // resume where we left off (without trying to catch up on the time we were
+                // disconnected)
+                start = Instant::now();
+                playtime = duration;
+            }
+            id += 1;
 
-            let playtime = Duration::from_millis((secs * 1000.0) as u64);
             let elapsed = Instant::now() - start;
             if playtime > elapsed {
                 thread::sleep(playtime - elapsed);
@@ -395,19 +1162,15 @@ fn audio_broadcast(
             if ctx.should_stop() {
                 return Ok(());
             }
+            if ctx.was_paused() {
+                // resume where we left off (without trying to catch up on the time we were
+                // paused)
+                start = Instant::now();
+                playtime = Duration::from_secs(0);
+            }
         }
 
-        debug!("TOTAL SIZE: {}", size);
-
-        // 32 kBit/s
-        let secs = (size * 8) as f64 / 1024.0 / 32.0;
-        debug!("SECONDS: {}", secs);
-
-        let playtime = Duration::from_millis((secs * 1000.0) as u64);
-        let elapsed = Instant::now() - start;
-        if playtime > elapsed {
-            thread::sleep(playtime - elapsed);
-        }
+        debug!("Finished playing {} ({:?})", path.to_string_lossy(), playtime);
 
         if ctx.should_stop_timeout(Duration::from_secs(3)) {
             return Ok(());
@@ -417,230 +1180,145 @@ fn audio_broadcast(
     Ok(())
 }
 
-fn pack_frame(sguid: &str, id: u64, freq: u64, rd: &Vec<u8>) -> Result<Vec<u8>, io::Error> {
-    let mut frame = Cursor::new(Vec::with_capacity(MAX_FRAME_LENGTH));
-
-    // header segment will be written at the end
-    frame.set_position(6);
-
-    // - AUDIO SEGMENT
-    let len_before = frame.position();
-    // AudioPart1
-    frame.write_all(&rd)?;
-    let len_audio_part = frame.position() - len_before;
-
-    // - FREQUENCY SEGMENT
-    let len_before = frame.position();
-    // Frequency
-    frame.write_f64::<LittleEndian>(freq as f64)?;
-    // Modulation
-    //    AM = 0,
-    //    FM = 1,
-    //    INTERCOM = 2,
-    //    DISABLED = 3
-    frame.write_all(&[0])?;
-    // Encryption
-    //    NO_ENCRYPTION = 0,
-    //    ENCRYPTION_JUST_OVERLAY = 1,
-    //    ENCRYPTION_FULL = 2,
-    //    ENCRYPTION_COCKPIT_TOGGLE_OVERLAY_CODE = 3
-    frame.write_all(&[0])?;
-    let len_frequency = frame.position() - len_before;
-
-    // - FIXED SEGMENT
-    // UnitId
-    frame.write_u32::<LittleEndian>(0)?;
-    // PacketId
-    frame.write_u64::<LittleEndian>(id)?;
-    // GUID
-    frame.write_all(sguid.as_bytes())?;
-
-    // - HEADER SEGMENT
-    let len_packet = frame.get_ref().len();
-    frame.set_position(0);
-    // Packet Length
-    frame.write_u16::<LittleEndian>(len_packet as u16)?;
-    // AudioPart1 Length
-    frame.write_u16::<LittleEndian>(len_audio_part as u16)?;
-    // FrequencyPart Length
-    frame.write_u16::<LittleEndian>(len_frequency as u16)?;
-
-    Ok(frame.into_inner())
-}
-
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
-pub struct Position {
-    pub x: f64,
-    #[serde(rename = "z")]
-    pub y: f64,
-    #[serde(rename = "y")]
-    pub alt: f64,
+/// Returns the position that is sent to SRS for a station at the given position.
+fn los_position(position: &Position) -> Position {
+    let mut position = position.clone();
+    position.alt += LOS_ALTITUDE_OFFSET; // increase sending alt to 100m above ground for LOS
+    position
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum MsgType {
-    Update,
-    Sync,
+/// Returns the latitude and longitude that is sent to SRS for a station at the given position.
+fn los_lat_lng_position(position: &LatLngPosition) -> LatLngPosition {
+    let mut position = position.clone();
+    position.alt += LOS_ALTITUDE_OFFSET;
+    position
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum Coalition {
-    Blue,
-    Red,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct Radio<'a> {
-    enc: bool,
-    enc_key: u8,
-    enc_mode: u8,
-    freq_max: f64,   // 1.0,
-    freq_min: f64,   // 1.0,
-    freq: f64,       // 1.0,
-    modulation: u8,  // 3,
-    name: &'a str,   // "No Radio",
-    sec_freq: f64,   // 0.0,
-    volume: f32,     // 1.0,
-    freq_mode: u8,   // 0,
-    vol_mode: u8,    // 0,
-    expansion: bool, // false,
-    channel: i32,    // -1,
-    simul: bool,     // false
-}
+/// Calls `connect` until it succeeds, waiting with an exponential backoff in between the attempts.
+/// Returns `None` if the worker got stopped while waiting.
+fn reconnect<T, F>(ctx: &Context, mut connect: F) -> Option<T>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    let mut backoff = Duration::from_secs(RECONNECT_BACKOFF_MIN_SECS);
+    loop {
+        if ctx.should_stop_timeout(backoff) {
+            return None;
+        }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct RadioInfo<'a> {
-    name: &'a str,
-    pos: Position,
-    ptt: bool,
-    radios: Vec<Radio<'a>>,
-    control: u8,
-    selected: usize,
-    unit: &'a str,
-    unit_id: usize,
-    simultaneous_transmission: bool,
+        match connect() {
+            Ok(t) => {
+                info!("Reconnected to SRS");
+                return Some(t);
+            }
+            Err(err) => {
+                warn!("Error reconnecting to SRS (retrying in {:?}): {}", backoff, err);
+                backoff = (backoff * 2).min(Duration::from_secs(RECONNECT_BACKOFF_MAX_SECS));
+            }
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct Client<'a> {
-    client_guid: &'a str,
-    name: &'a str,
-    position: Position,
-    coalition: Coalition,
-    radio_info: Option<RadioInfo<'a>>,
-    // ClientChannelId
+/// The address of a SRS server. The host can either be an IP address (v4 or v6) or a hostname,
+/// which is resolved through DNS when connecting.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Server {
+    host: String,
+    port: u16,
+    voice_port: Option<u16>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct Message<'a> {
-    client: Option<Client<'a>>,
-    msg_type: MsgType,
-    // Clients
-    // ServerSettings
-    // ExternalAWACSModePassword
-    version: &'a str,
-}
+impl Server {
+    pub fn new(host: &str, port: u16) -> Self {
+        // allow IPv6 addresses to be provided in their bracketed form, e.g. `[::1]`
+        let host = host.trim_start_matches('[').trim_end_matches(']');
 
-impl ::serde::Serialize for MsgType {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: ::serde::Serializer,
-    {
-        // Serialize the enum as a u64.
-        serializer.serialize_u64(match *self {
-            MsgType::Update => 1,
-            MsgType::Sync => 2,
-        })
+        Server {
+            host: host.to_string(),
+            port,
+            voice_port: None,
+        }
     }
-}
-
-impl<'de> ::serde::Deserialize<'de> for MsgType {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: ::serde::Deserializer<'de>,
-    {
-        struct Visitor;
-
-        impl<'de> ::serde::de::Visitor<'de> for Visitor {
-            type Value = MsgType;
 
-            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-                formatter.write_str("positive integer")
-            }
+    /// Sets the port used for voice packets. If not set, the port following the sync port is used
+    /// for TCP, and the sync port itself for UDP (which is what SRS does by default). It must be set
+    /// for TCP if the sync port is 65535, since there is no port following it.
+    pub fn set_voice_port(&mut self, port: u16) {
+        self.voice_port = Some(port);
+    }
 
-            fn visit_u64<E>(self, value: u64) -> Result<MsgType, E>
-            where
-                E: ::serde::de::Error,
-            {
-                // Rust does not come with a simple way of converting a
-                // number to an enum, so use a big `match`.
-                match value {
-                    1 => Ok(MsgType::Update),
-                    2 => Ok(MsgType::Sync),
-                    _ => Err(E::custom(format!(
-                        "unknown {} value: {}",
-                        stringify!(MsgType),
-                        value
-                    ))),
-                }
-            }
-        }
+    fn sync_addr(&self) -> (&str, u16) {
+        (&self.host, self.port)
+    }
 
-        // Deserialize the enum from a u64.
-        deserializer.deserialize_u64(Visitor)
+    fn voice_addr(&self, transport: VoiceTransport) -> Result<(&str, u16), Error> {
+        let port = match (self.voice_port, transport) {
+            (Some(port), _) => port,
+            (None, VoiceTransport::Tcp) => self
+                .port
+                .checked_add(1)
+                .ok_or(Error::NoVoicePort(self.port))?,
+            (None, VoiceTransport::Udp) => self.port,
+        };
+        Ok((&self.host, port))
     }
 }
 
-impl ::serde::Serialize for Coalition {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: ::serde::Serializer,
-    {
-        // Serialize the enum as a u64.
-        serializer.serialize_u64(match *self {
-            Coalition::Blue => 2,
-            Coalition::Red => 1,
-        })
+impl Default for Server {
+    fn default() -> Self {
+        Server::new("127.0.0.1", 5002)
     }
 }
 
-impl<'de> ::serde::Deserialize<'de> for Coalition {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: ::serde::Deserializer<'de>,
-    {
-        struct Visitor;
+/// A frequency (in Hz), the modulation used on it and whether it is encrypted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Frequency {
+    pub freq: u64,
+    pub modulation: Modulation,
+    pub encryption: Option<Encryption>,
+}
 
-        impl<'de> ::serde::de::Visitor<'de> for Visitor {
-            type Value = Coalition;
+impl Frequency {
+    pub fn new(freq: u64, modulation: Modulation) -> Self {
+        Frequency {
+            freq,
+            modulation,
+            encryption: None,
+        }
+    }
+}
 
-            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-                formatter.write_str("positive integer")
-            }
+/// The SRS radio encryption settings. Only clients that use the same encryption key on their
+/// radio are able to understand an encrypted broadcast.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Encryption {
+    key: u8,
+    pub mode: EncryptionMode,
+}
 
-            fn visit_u64<E>(self, value: u64) -> Result<Coalition, E>
-            where
-                E: ::serde::de::Error,
-            {
-                // Rust does not come with a simple way of converting a
-                // number to an enum, so use a big `match`.
-                match value {
-                    1 => Ok(Coalition::Red),
-                    2 => Ok(Coalition::Blue),
-                    _ => Err(E::custom(format!(
-                        "unknown {} value: {}",
-                        stringify!(Coalition),
-                        value
-                    ))),
-                }
-            }
+impl Encryption {
+    /// Creates the settings for the given encryption key. SRS uses keys from 1 to 252 (a key of 0
+    /// is sent for unencrypted broadcasts), other keys are rejected.
+    pub fn new(key: u8) -> Result<Self, Error> {
+        if key < 1 || key > 252 {
+            return Err(Error::InvalidEncryptionKey(key));
         }
 
-        // Deserialize the enum from a u64.
-        deserializer.deserialize_u64(Visitor)
+        Ok(Encryption {
+            key,
+            mode: EncryptionMode::Full,
+        })
     }
+
+    /// The encryption key (from 1 to 252).
+    pub fn key(&self) -> u8 {
+        self.key
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EncryptionMode {
+    JustOverlay = 1,
+    Full = 2,
+    CockpitToggleOverlayCode = 3,
 }
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::voice::VoiceSocket;
+use crate::{Error, Player, PlayerHandle, VoiceTransport};
+
+/// Runs multiple stations side by side, each identified by a unique id (e.g. the name of the static
+/// it was created from).
+///
+/// Every station is its own SRS client with its own client GUID (stations are given a new one when
+/// they are started, so that they can be cloned from the same player) and its own sync connection
+/// (SRS identifies clients by their sync connection, so it cannot be shared between stations).
+/// Stations broadcasting via UDP to the same server share a single UDP voice socket though, with
+/// each station registering its GUID through its own pings.
+#[derive(Default)]
+pub struct StationManager {
+    stations: BTreeMap<String, PlayerHandle>,
+    /// The shared UDP voice sockets, keyed by the SRS voice address they are connected to.
+    voice_sockets: HashMap<(String, u16), VoiceSocket>,
+}
+
+impl StationManager {
+    pub fn new() -> Self {
+        StationManager::default()
+    }
+
+    /// Starts playing the given audio file (or all audio files inside the given directory) as the
+    /// station `id` (see `Player::spawn`). Fails with `Error::StationExists` if a station with the
+    /// same id is still running.
+    pub fn spawn<P: AsRef<Path>>(
+        &mut self,
+        id: &str,
+        mut player: Player,
+        path: P,
+        should_loop: bool,
+    ) -> Result<(), Error> {
+        self.check_id(id)?;
+        player.regenerate_guid();
+        if player.voice_transport == VoiceTransport::Udp {
+            player.voice_socket = Some(self.voice_socket(&player)?);
+        }
+        let handle = player.spawn(path, should_loop)?;
+        self.insert(id, handle);
+
+        Ok(())
+    }
+
+    /// Starts recording the given player's frequencies into the given directory as the station `id`
+    /// (see `Player::record`). Recording stations keep a voice connection of their own, since they
+    /// must only receive the packets SRS sends to them. Fails with `Error::StationExists` if a station with the same id is
+    /// still running.
+    pub fn record<P: AsRef<Path>>(
+        &mut self,
+        id: &str,
+        mut player: Player,
+        dir: P,
+    ) -> Result<(), Error> {
+        self.check_id(id)?;
+        player.regenerate_guid();
+        let handle = player.record(dir)?;
+        self.insert(id, handle);
+
+        Ok(())
+    }
+
+    /// Stops the station with the given id. Returns `false` if there is no such station.
+    pub fn stop(&mut self, id: &str) -> bool {
+        match self.stations.remove(id) {
+            Some(handle) => {
+                handle.stop();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops all stations.
+    pub fn stop_all(&mut self) {
+        for (_, handle) in std::mem::take(&mut self.stations) {
+            handle.stop();
+        }
+        self.voice_sockets.clear();
+    }
+
+    pub fn pause_all(&self) {
+        for handle in self.stations.values() {
+            handle.pause();
+        }
+    }
+
+    pub fn unpause_all(&self) {
+        for handle in self.stations.values() {
+            handle.unpause();
+        }
+    }
+
+    /// Stops and removes all stations whose playback finished (or failed) and returns their ids.
+    pub fn remove_finished(&mut self) -> Vec<String> {
+        let finished: Vec<String> = self
+            .stations
+            .iter()
+            .filter(|(_, handle)| !handle.is_running())
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &finished {
+            self.stop(id);
+        }
+
+        finished
+    }
+
+    /// Returns the handle of the station with the given id, e.g. to move or retune it.
+    pub fn get(&self, id: &str) -> Option<&PlayerHandle> {
+        self.stations.get(id)
+    }
+
+    /// Returns all stations (ordered by their id).
+    pub fn stations(&self) -> impl Iterator<Item = (&str, &PlayerHandle)> {
+        self.stations
+            .iter()
+            .map(|(id, handle)| (id.as_str(), handle))
+    }
+
+    /// Returns the ids of all stations (in order).
+    pub fn ids(&self) -> Vec<String> {
+        self.stations.keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+
+    fn check_id(&mut self, id: &str) -> Result<(), Error> {
+        match self.stations.get(id) {
+            Some(handle) if handle.is_running() => Err(Error::StationExists(id.to_string())),
+            Some(_) => {
+                // replace a station whose playback already finished
+                self.stop(id);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the UDP voice socket connected to the given player's SRS server, which is created if
+    /// there is none yet.
+    fn voice_socket(&mut self, player: &Player) -> Result<VoiceSocket, Error> {
+        let (host, port) = player.server.voice_addr(VoiceTransport::Udp)?;
+        let key = (host.to_string(), port);
+        if let Some(socket) = self.voice_sockets.get(&key) {
+            return Ok(socket.clone());
+        }
+
+        let socket = VoiceSocket::connect((host, port))?;
+        self.voice_sockets.insert(key, socket.clone());
+        Ok(socket)
+    }
+
+    fn insert(&mut self, id: &str, handle: PlayerHandle) {
+        info!("Started station {}", id);
+        self.stations.insert(id.to_string(), handle);
+    }
+}
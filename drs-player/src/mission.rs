@@ -0,0 +1,100 @@
+use std::path::{Component, PathBuf};
+use std::str::FromStr;
+
+use crate::{Frequency, Player};
+use drs_protocol::{Coalition, Modulation, Position};
+use regex::Regex;
+
+/// The frequency used if the name of a station does not contain one (255 MHz).
+const DEFAULT_FREQ: u64 = 255_000_000;
+
+/// The pattern for names of statics (and units) that are turned into stations, e.g.
+/// `SRS Player 251.000 AM blue music/` (everything after `SRS Player` is optional).
+const STATION_NAME_PATTERN: &str = r"(?i)^SRS Player(?:\s+(?P<freq>\d+(?:\.\d+)?))?(?:\s+(?P<mod>AM|FM))?(?:\s+(?P<coalition>blue|red|neutrals?|spectator))?(?:\s+(?P<path>.*?))?\s*$";
+
+lazy_static! {
+    static ref STATION_NAME_RE: Regex = Regex::new(STATION_NAME_PATTERN).unwrap();
+}
+
+/// The station settings parsed from the name of a static (or unit) in the mission.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StationConfig {
+    /// The frequency (in Hz) and modulation (defaults to 255 MHz AM)
+    pub freq: Frequency,
+    /// The coalition (defaults to the coalition of the static)
+    pub coalition: Option<Coalition>,
+    /// The playlist (audio file or directory), relative to the path the stations are started with
+    pub path: Option<PathBuf>,
+}
+
+impl StationConfig {
+    /// Parses the given static name. The name consists of `SRS Player`, optionally followed by the
+    /// frequency in MHz, the modulation, the coalition and the playlist path (in this order), e.g.
+    /// `SRS Player 251.000 AM blue music/`. Returns `None` if the name does not match this pattern
+    /// or if the playlist path is absolute or contains `..`, since it must not point outside of the
+    /// path the stations are started with.
+    pub fn parse(name: &str) -> Option<StationConfig> {
+        let caps = STATION_NAME_RE.captures(name)?;
+
+        let freq = match caps.name("freq") {
+            Some(freq) => (f64::from_str(freq.as_str()).ok()? * 1_000_000.0).round() as u64,
+            None => DEFAULT_FREQ,
+        };
+        let modulation = match caps.name("mod") {
+            Some(modulation) => Modulation::from_str(modulation.as_str()).ok()?,
+            None => Modulation::AM,
+        };
+        let coalition = match caps.name("coalition") {
+            Some(coalition) => Some(Coalition::from_str(coalition.as_str()).ok()?),
+            None => None,
+        };
+        let path = caps
+            .name("path")
+            .map(|path| path.as_str())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        if let Some(ref path) = path {
+            let is_relative = path
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !is_relative {
+                return None;
+            }
+        }
+
+        Some(StationConfig {
+            freq: Frequency::new(freq, modulation),
+            coalition,
+            path,
+        })
+    }
+
+    /// The name the station is displayed with in SRS, e.g. `SRS Radio 251.000 AM`. Unlike the
+    /// static name, it does not contain the playlist path and coalition.
+    pub fn name(&self) -> String {
+        format!(
+            "SRS Radio {:.3} {:?}",
+            self.freq.freq as f64 / 1_000_000.0,
+            self.freq.modulation
+        )
+    }
+
+    /// Creates a player for the station at the given position. It broadcasts for the coalition
+    /// from the name, or for `side` (the coalition of the static) if the name does not contain one.
+    pub fn player(&self, position: Position, side: Coalition) -> Player {
+        let mut player = Player::new(&self.name(), position, self.freq.freq);
+        player.freqs = vec![self.freq];
+        player.set_coalition(self.coalition.unwrap_or(side));
+        player
+    }
+}
+
+/// A station that was found in the mission (see `Player::create`).
+pub struct MissionStation {
+    /// The name of the static (or unit) the station was created from, which identifies the station
+    pub id: String,
+    pub player: Player,
+    /// The playlist from the name of the static (if any), which is relative to the path the
+    /// stations are started with
+    pub path: Option<PathBuf>,
+}
@@ -0,0 +1,245 @@
+use std::io::{Read, Seek, Write};
+use std::time::Duration;
+
+use crate::Error;
+use ogg::reading::PacketReader;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use ogg::Packet;
+use uuid::Uuid;
+
+/// Opus always uses a 48 kHz clock for durations and granule positions, independent of the sample
+/// rate the audio was encoded with.
+const SAMPLE_RATE: u64 = 48_000;
+
+/// An Opus packet must not contain more than 120ms of audio.
+const MAX_PACKET_SAMPLES: u32 = 5760;
+
+/// The Opus ID header (see https://tools.ietf.org/html/rfc7845#section-5.1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpusHead {
+    pub channels: u8,
+    /// The number of samples (at 48 kHz) to discard from the decoder output when starting
+    /// playback
+    pub pre_skip: u16,
+    /// The sample rate of the original input (informational only)
+    pub input_sample_rate: u32,
+}
+
+impl OpusHead {
+    /// Parses the given Ogg packet, returning `None` if it is not an Opus ID header.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if !data.starts_with(b"OpusHead") || data.len() < 19 {
+            return None;
+        }
+
+        Some(OpusHead {
+            channels: data[9],
+            pre_skip: u16::from_le_bytes([data[10], data[11]]),
+            input_sample_rate: u32::from_le_bytes([data[12], data[13], data[14], data[15]]),
+        })
+    }
+}
+
+/// Returns the number of samples (at 48 kHz) contained in the given Opus packet, based on its TOC
+/// byte (see https://tools.ietf.org/html/rfc6716#section-3.1). Returns `None` for packets that
+/// are not valid Opus audio packets.
+pub fn packet_samples(packet: &[u8]) -> Option<u32> {
+    let toc = *packet.first()?;
+    let frame_count = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => u32::from(*packet.get(1)? & 0x3F),
+    };
+    if frame_count == 0 {
+        return None;
+    }
+
+    let samples = frame_count * frame_samples(toc);
+    if samples > MAX_PACKET_SAMPLES {
+        None
+    } else {
+        Some(samples)
+    }
+}
+
+/// Returns the playback duration of the given Opus packet, or `None` if it is not a valid Opus
+/// audio packet.
+pub fn packet_duration(packet: &[u8]) -> Option<Duration> {
+    packet_samples(packet).map(samples_to_duration)
+}
+
+/// Converts a number of samples (at 48 kHz) into a duration.
+pub fn samples_to_duration(samples: u32) -> Duration {
+    Duration::from_micros(u64::from(samples) * 1_000_000 / SAMPLE_RATE)
+}
+
+/// Returns the number of samples (at 48 kHz) of each frame of a packet with the given TOC byte.
+pub fn frame_samples(toc: u8) -> u32 {
+    let config = toc >> 3;
+    match config {
+        // SILK-only: 10, 20, 40, 60ms
+        0..=11 => [480, 960, 1920, 2880][usize::from(config % 4)],
+        // Hybrid: 10, 20ms
+        12..=15 => [480, 960][usize::from(config % 2)],
+        // CELT-only: 2.5, 5, 10, 20ms
+        _ => [120, 240, 480, 960][usize::from(config % 4)],
+    }
+}
+
+/// A packet read by `OggOpusReader`.
+pub enum OggOpusPacket {
+    /// The ID header starting a new Opus stream.
+    Head(OpusHead),
+    /// The comment header (without its `OpusTags` magic signature).
+    Tags(Vec<u8>),
+    /// An audio packet.
+    Audio(Packet),
+}
+
+/// Reads the Opus streams of an Ogg file. Logical streams that are not Opus (e.g. a multiplexed
+/// video stream) are ignored, and chained Opus streams are read one after another (even if a
+/// stream ends without an end of stream page, like the links of truncated stream rips often do).
+/// Only a single Opus stream is read at a time, Opus streams multiplexed with the one currently
+/// read are ignored as well.
+pub struct OggOpusReader<R: Read + Seek> {
+    reader: PacketReader<R>,
+    /// The serial of the Opus stream currently read.
+    serial: Option<u32>,
+    /// Whether the data pages of the current chain link have started. The beginning of stream
+    /// pages of all multiplexed streams precede them, so every later one starts the next link.
+    data_started: bool,
+}
+
+impl<R: Read + Seek> OggOpusReader<R> {
+    pub fn new(rd: R) -> Self {
+        OggOpusReader {
+            reader: PacketReader::new(rd),
+            serial: None,
+            data_started: false,
+        }
+    }
+
+    /// Reads the next packet of the current Opus stream (or the ID header of the next one).
+    pub fn read_packet(&mut self) -> Result<Option<OggOpusPacket>, Error> {
+        while let Some(pck) = self.reader.read_packet()? {
+            let serial = pck.stream_serial();
+
+            // the first packet of a logical stream identifies its codec
+            if pck.first_in_stream() {
+                if self.serial.is_none() || self.data_started {
+                    if let Some(head) = OpusHead::parse(&pck.data) {
+                        if let Some(prev) = self.serial {
+                            debug!(
+                                "Opus stream {:#010x} ended without end of stream page",
+                                prev
+                            );
+                        }
+                        self.serial = Some(serial);
+                        self.data_started = false;
+                        return Ok(Some(OggOpusPacket::Head(head)));
+                    }
+                }
+                debug!("Ignoring logical Ogg stream {:#010x}", serial);
+                continue;
+            }
+            self.data_started = true;
+
+            if self.serial != Some(serial) {
+                continue;
+            }
+            if pck.last_in_stream() {
+                // a chained stream may follow
+                self.serial = None;
+            }
+
+            if pck.data.starts_with(b"OpusTags") {
+                return Ok(Some(OggOpusPacket::Tags(pck.data[8..].to_vec())));
+            }
+            return Ok(Some(OggOpusPacket::Audio(pck)));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Writes a mono Ogg/Opus stream (see https://tools.ietf.org/html/rfc7845).
+pub struct OggOpusWriter<W: Write> {
+    writer: PacketWriter<W>,
+    serial: u32,
+    /// The number of samples (at 48 kHz) written so far, including the pre-skip.
+    granule: u64,
+    /// The last written packet (and the granule position at its end), which is only written once
+    /// it is known whether it ends the stream.
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl<W: Write> OggOpusWriter<W> {
+    /// Creates a new stream and writes its ID and comment header. The `pre_skip` is the number of
+    /// samples (at 48 kHz) to discard from the decoder output when starting playback.
+    pub fn new(out: W, input_sample_rate: u32, pre_skip: u16) -> Result<Self, Error> {
+        let mut serial = [0; 4];
+        serial.copy_from_slice(&Uuid::new_v4().as_bytes()[..4]);
+        let serial = u32::from_le_bytes(serial);
+
+        let mut writer = PacketWriter::new(out);
+
+        // ID header (see https://tools.ietf.org/html/rfc7845#section-5.1)
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // version
+        head.push(1); // channel count (SRS voice is mono)
+        head.extend_from_slice(&pre_skip.to_le_bytes());
+        head.extend_from_slice(&input_sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+        head.push(0); // channel mapping family
+        writer.write_packet(head.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        // comment header (see https://tools.ietf.org/html/rfc7845#section-5.2)
+        let vendor = concat!("drsplayer ", env!("CARGO_PKG_VERSION"));
+        let mut tags = Vec::new();
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes()); // comment count
+        writer.write_packet(tags.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        Ok(OggOpusWriter {
+            writer,
+            serial,
+            granule: u64::from(pre_skip),
+            pending: None,
+        })
+    }
+
+    /// Appends the given Opus packet to the stream.
+    pub fn write(&mut self, packet: &[u8]) -> Result<(), Error> {
+        self.granule += u64::from(packet_samples(packet).unwrap_or(0));
+        if let Some((data, granule)) = self.pending.replace((packet.to_vec(), self.granule)) {
+            self.writer.write_packet(
+                data.into_boxed_slice(),
+                self.serial,
+                PacketWriteEndInfo::NormalPacket,
+                granule,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the stream and returns the underlying writer. If `end_granule` is provided and smaller
+    /// than the granule position after the last packet, the decoded audio is cut off at that
+    /// position (used to strip the padding of the last packet).
+    pub fn finish(mut self, end_granule: Option<u64>) -> Result<W, Error> {
+        if let Some((data, granule)) = self.pending.take() {
+            let granule = end_granule.map(|g| g.min(granule)).unwrap_or(granule);
+            self.writer.write_packet(
+                data.into_boxed_slice(),
+                self.serial,
+                PacketWriteEndInfo::EndStream,
+                granule,
+            )?;
+        }
+
+        Ok(self.writer.into_inner())
+    }
+}
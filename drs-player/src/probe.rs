@@ -0,0 +1,381 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::opus::{self, OggOpusPacket, OggOpusReader};
+use crate::{AudioFormat, Error};
+use drs_protocol::VoicePacket;
+#[cfg(feature = "transcode")]
+use ogg::reading::PacketReader;
+
+/// The properties of an audio file, as reported by `probe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioInfo {
+    pub format: AudioFormat,
+    pub channels: u16,
+    /// The sample rate in Hz (for Opus, the sample rate of the original input, as Opus itself
+    /// always uses 48 kHz)
+    pub sample_rate: u32,
+    /// The average bitrate in bits per second
+    pub bitrate: Option<u32>,
+    /// The (distinct) durations of the packets of an Opus stream (each packet is sent as one
+    /// voice frame)
+    pub frame_durations: Vec<Duration>,
+    pub duration: Option<Duration>,
+    /// The vendor string of an Opus stream
+    pub vendor: Option<String>,
+    /// The user comments of an Opus stream (e.g. `TITLE=...`)
+    pub tags: Vec<String>,
+    /// Everything that prevents the file from being played correctly over SRS
+    pub problems: Vec<Problem>,
+}
+
+/// A reason why an audio file cannot be played correctly over SRS.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The Opus stream is not mono (SRS voice is mono).
+    Channels(u16),
+    /// The given number of packets of the Opus stream are too large to be sent in a voice packet.
+    PacketSize(usize),
+    /// The given number of packets of the Opus stream are not valid Opus audio packets.
+    InvalidPackets(usize),
+    /// The duration of the file could not be determined.
+    UnknownDuration,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Channels(channels) => write!(
+                f,
+                "Opus stream has {} channels, but SRS only plays mono streams",
+                channels
+            ),
+            Problem::PacketSize(count) => write!(
+                f,
+                "Opus stream contains {} packet(s) larger than {} bytes",
+                count,
+                VoicePacket::max_audio_length(1)
+            ),
+            Problem::InvalidPackets(count) => {
+                write!(f, "Opus stream contains {} invalid packet(s)", count)
+            }
+            Problem::UnknownDuration => write!(f, "Failed reading the duration"),
+        }
+    }
+}
+
+impl Problem {
+    /// Whether the problem is fixed by transcoding the file before it is played (which requires
+    /// the `transcode` feature).
+    pub fn is_fixed_by_transcoding(&self) -> bool {
+        cfg!(feature = "transcode") && self.requires_transcoding()
+    }
+
+    fn requires_transcoding(&self) -> bool {
+        match self {
+            Problem::Channels(_) | Problem::PacketSize(_) => true,
+            Problem::InvalidPackets(_) | Problem::UnknownDuration => false,
+        }
+    }
+}
+
+impl AudioInfo {
+    fn new(format: AudioFormat, channels: u16, sample_rate: u32) -> Self {
+        AudioInfo {
+            format,
+            channels,
+            sample_rate,
+            bitrate: None,
+            frame_durations: Vec::new(),
+            duration: None,
+            vendor: None,
+            tags: Vec::new(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Whether the file is transcoded before it is played, which is the case for all files that
+    /// are not Ogg/Opus, and for Opus streams SRS clients cannot decode (e.g. stereo streams).
+    pub fn needs_transcoding(&self) -> bool {
+        self.format != AudioFormat::Opus || self.problems.iter().any(Problem::requires_transcoding)
+    }
+
+    /// Whether the file can be played correctly over SRS, after transcoding it if necessary. This
+    /// applies the same rule to all formats: only problems that remain after transcoding count.
+    pub fn is_playable(&self) -> bool {
+        self.problems.iter().all(Problem::is_fixed_by_transcoding)
+    }
+
+    /// Sets the duration and derives the average bitrate from it and the given file size.
+    fn set_duration(&mut self, duration: Duration, size: u64) {
+        if duration > Duration::from_secs(0) {
+            let secs = duration.as_micros() as f64 / 1_000_000.0;
+            self.bitrate = Some((size as f64 * 8.0 / secs).round() as u32);
+        }
+        self.duration = Some(duration);
+    }
+}
+
+impl AudioFormat {
+    /// The container format, e.g. `Ogg`.
+    pub fn container(self) -> &'static str {
+        match self {
+            AudioFormat::Opus | AudioFormat::Vorbis => "Ogg",
+            AudioFormat::Mp3 => "MPEG audio",
+            AudioFormat::Wav => "WAV",
+            AudioFormat::Flac => "FLAC",
+        }
+    }
+
+    /// The audio codec, e.g. `Opus`.
+    pub fn codec(self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "Opus",
+            AudioFormat::Vorbis => "Vorbis",
+            AudioFormat::Mp3 => "MP3",
+            AudioFormat::Wav => "PCM",
+            AudioFormat::Flac => "FLAC",
+        }
+    }
+}
+
+/// Reads the properties of the given audio file and checks whether it can be played over SRS.
+/// Files that are not Ogg/Opus are transcoded before they are played, so only problems that
+/// prevent them from being decoded at all are reported (as errors). Probing them requires the
+/// `transcode` feature.
+pub fn probe<P: AsRef<Path>>(path: P) -> Result<AudioInfo, Error> {
+    let path = path.as_ref();
+    let size = fs::metadata(path)?.len();
+    let format = AudioFormat::detect(path)?.ok_or_else(|| Error::UnsupportedFormat(path.into()))?;
+
+    let mut info = match format {
+        AudioFormat::Opus => probe_opus(path, size)?,
+        #[cfg(feature = "transcode")]
+        AudioFormat::Vorbis => probe_vorbis(path, size)?,
+        #[cfg(feature = "transcode")]
+        AudioFormat::Mp3 => probe_mp3(path, size)?,
+        #[cfg(feature = "transcode")]
+        AudioFormat::Wav => {
+            let reader = hound::WavReader::open(path)?;
+            let spec = reader.spec();
+            let mut info = AudioInfo::new(format, spec.channels, spec.sample_rate);
+            info.duration = Some(samples_to_duration(
+                u64::from(reader.duration()),
+                spec.sample_rate,
+            ));
+            info.bitrate =
+                Some(spec.sample_rate * u32::from(spec.channels) * u32::from(spec.bits_per_sample));
+            info
+        }
+        #[cfg(feature = "transcode")]
+        AudioFormat::Flac => {
+            let reader = claxon::FlacReader::open(path)?;
+            let stream = reader.streaminfo();
+            let mut info = AudioInfo::new(format, stream.channels as u16, stream.sample_rate);
+            if let Some(samples) = stream.samples {
+                info.set_duration(samples_to_duration(samples, stream.sample_rate), size);
+            }
+            info
+        }
+        // the decoders of all other formats are only available along with the transcoder
+        #[cfg(not(feature = "transcode"))]
+        _ => return Err(Error::TranscodingDisabled(path.into())),
+    };
+
+    if info.duration.is_none() {
+        info.problems.push(Problem::UnknownDuration);
+    }
+
+    Ok(info)
+}
+
+fn probe_opus(path: &Path, size: u64) -> Result<AudioInfo, Error> {
+    let mut reader = OggOpusReader::new(BufReader::new(File::open(path)?));
+
+    let head = match reader.read_packet()? {
+        Some(OggOpusPacket::Head(head)) => head,
+        _ => return Err(Error::UnsupportedFormat(path.into())),
+    };
+    let mut info = AudioInfo::new(
+        AudioFormat::Opus,
+        u16::from(head.channels),
+        head.input_sample_rate,
+    );
+    check_channels(&mut info, head.channels);
+
+    // the streams of chained files are played one after another
+    let mut streams = 1;
+    let mut pre_skip = u64::from(head.pre_skip);
+    let mut granule: Option<u64> = None;
+    let mut samples: Option<u64> = None;
+
+    let mut invalid = 0;
+    let mut oversized = 0;
+    while let Some(pck) = reader.read_packet()? {
+        let pck = match pck {
+            OggOpusPacket::Head(head) => {
+                if let Some(granule) = granule.take() {
+                    *samples.get_or_insert(0) += granule.saturating_sub(pre_skip);
+                }
+                streams += 1;
+                pre_skip = u64::from(head.pre_skip);
+                check_channels(&mut info, head.channels);
+                continue;
+            }
+            // comment header (see https://tools.ietf.org/html/rfc7845#section-5.2), only the one
+            // of the first stream is reported
+            OggOpusPacket::Tags(data) => {
+                if streams == 1 {
+                    let (vendor, tags) = read_comments(&data);
+                    info.vendor = vendor;
+                    info.tags = tags;
+                }
+                continue;
+            }
+            OggOpusPacket::Audio(pck) => pck,
+        };
+
+        if pck.last_in_page() {
+            granule = Some(pck.absgp_page());
+        }
+        if pck.data.len() > VoicePacket::max_audio_length(1) {
+            oversized += 1;
+        }
+        let frame_duration = match opus::packet_duration(&pck.data) {
+            Some(duration) => duration,
+            None => {
+                invalid += 1;
+                continue;
+            }
+        };
+        if !info.frame_durations.contains(&frame_duration) {
+            info.frame_durations.push(frame_duration);
+        }
+    }
+    if let Some(granule) = granule {
+        *samples.get_or_insert(0) += granule.saturating_sub(pre_skip);
+    }
+
+    if invalid > 0 {
+        info.problems.push(Problem::InvalidPackets(invalid));
+    }
+    if oversized > 0 {
+        info.problems.push(Problem::PacketSize(oversized));
+    }
+    if let Some(samples) = samples {
+        info.set_duration(samples_to_duration(samples, 48_000), size);
+    }
+
+    Ok(info)
+}
+
+/// Reports Opus streams that are not mono (once per channel count).
+fn check_channels(info: &mut AudioInfo, channels: u8) {
+    let problem = Problem::Channels(u16::from(channels));
+    if channels != 1 && !info.problems.contains(&problem) {
+        info.problems.push(problem);
+    }
+}
+
+#[cfg(feature = "transcode")]
+fn probe_vorbis(path: &Path, size: u64) -> Result<AudioInfo, Error> {
+    let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+    let head = match reader.read_packet()? {
+        Some(pck) => pck,
+        None => return Err(Error::UnsupportedFormat(path.into())),
+    };
+    let ident = lewton::header::read_header_ident(&head.data).map_err(lewton::VorbisError::from)?;
+    let mut info = AudioInfo::new(
+        AudioFormat::Vorbis,
+        u16::from(ident.audio_channels),
+        ident.audio_sample_rate,
+    );
+
+    let mut granule = None;
+    while let Some(pck) = reader.read_packet()? {
+        if pck.stream_serial() == head.stream_serial() && pck.last_in_page() {
+            granule = Some(pck.absgp_page());
+        }
+    }
+    if let Some(granule) = granule {
+        info.set_duration(samples_to_duration(granule, ident.audio_sample_rate), size);
+    }
+
+    Ok(info)
+}
+
+#[cfg(feature = "transcode")]
+fn probe_mp3(path: &Path, size: u64) -> Result<AudioInfo, Error> {
+    let mut decoder = minimp3::Decoder::new(BufReader::new(File::open(path)?));
+    let mut info: Option<AudioInfo> = None;
+    let mut samples = 0;
+    loop {
+        match decoder.next_frame() {
+            Ok(frame) => {
+                samples += frame.data.len().checked_div(frame.channels).unwrap_or(0) as u64;
+                if info.is_none() {
+                    info = Some(AudioInfo::new(
+                        AudioFormat::Mp3,
+                        frame.channels as u16,
+                        frame.sample_rate as u32,
+                    ));
+                }
+            }
+            Err(minimp3::Error::SkippedData) => continue,
+            Err(minimp3::Error::Eof) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let mut info = info.ok_or_else(|| Error::UnsupportedFormat(path.into()))?;
+    let sample_rate = info.sample_rate;
+    info.set_duration(samples_to_duration(samples, sample_rate), size);
+
+    Ok(info)
+}
+
+/// Reads the vendor string and the user comments of a Vorbis comment (as used by the Opus comment
+/// header), ignoring everything that is malformed.
+fn read_comments(mut data: &[u8]) -> (Option<String>, Vec<String>) {
+    let vendor = read_string(&mut data);
+    let mut tags = Vec::new();
+    if let Some(count) = read_u32(&mut data) {
+        for _ in 0..count {
+            match read_string(&mut data) {
+                Some(tag) => tags.push(tag),
+                None => break,
+            }
+        }
+    }
+
+    (vendor, tags)
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (n, rest) = data.split_at(4);
+    *data = rest;
+    Some(u32::from_le_bytes([n[0], n[1], n[2], n[3]]))
+}
+
+fn read_string(data: &mut &[u8]) -> Option<String> {
+    let len = read_u32(data)? as usize;
+    if data.len() < len {
+        return None;
+    }
+    let (s, rest) = data.split_at(len);
+    *data = rest;
+    Some(String::from_utf8_lossy(s).into_owned())
+}
+
+fn samples_to_duration(samples: u64, sample_rate: u32) -> Duration {
+    if sample_rate == 0 {
+        return Duration::from_secs(0);
+    }
+    Duration::from_micros(samples * 1_000_000 / u64::from(sample_rate))
+}
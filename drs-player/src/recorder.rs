@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::opus::{self, OggOpusWriter};
+use crate::worker::Context;
+use crate::{reconnect, Error, Player, Shared};
+use drs_protocol::VoicePacket;
+
+/// A transmission is considered to be over if no voice packet of its sender was received for this
+/// long.
+const TRANSMISSION_TIMEOUT_MILLIS: u64 = 1000;
+
+/// Receives the voice packets SRS forwards to the player and writes every transmission on one of
+/// the player's frequencies into its own Ogg/Opus file inside `dir`. Errors writing a recording
+/// are logged and only drop that recording (the rest of the transmission is not recorded).
+pub fn record(ctx: Context, player: Player, shared: &Shared, dir: PathBuf) -> Result<(), Error> {
+    let mut conn = player.connect_voice()?;
+    let timeout = Duration::from_millis(TRANSMISSION_TIMEOUT_MILLIS);
+
+    // the ongoing transmissions, keyed by the GUID of their sender
+    let mut recordings: HashMap<String, Recording> = HashMap::new();
+    // the ongoing transmissions whose recording failed, with the time of their last packet
+    let mut failed: HashMap<String, Instant> = HashMap::new();
+
+    loop {
+        let data = match conn.keep_alive().and_then(|_| conn.recv()) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Lost voice connection to SRS: {}", err);
+                for (_, recording) in recordings.drain() {
+                    recording.finish();
+                }
+                conn = match reconnect(&ctx, || player.connect_voice()) {
+                    Some(conn) => conn,
+                    None => return Ok(()),
+                };
+                continue;
+            }
+        };
+
+        if let Some(data) = data {
+            match VoicePacket::decode(&data) {
+                Ok(pck) => {
+                    let freq = player.receives(&shared.freqs.read().unwrap(), &pck);
+                    if let Some(freq) = freq {
+                        let guid = pck.guid.as_ref();
+                        if opus::packet_samples(&pck.audio).is_none() {
+                            debug!("Skipping packet that is not an opus audio packet");
+                        } else if let Some(last_packet) = failed.get_mut(guid) {
+                            *last_packet = Instant::now();
+                        } else {
+                            if !recordings.contains_key(guid) {
+                                let name = shared
+                                    .state
+                                    .read()
+                                    .unwrap()
+                                    .client(guid)
+                                    .map(|client| client.name.clone())
+                                    .unwrap_or_else(|| guid.to_string());
+                                match Recording::create(&dir, freq, guid, &name) {
+                                    Ok(recording) => {
+                                        debug!("Recording {}", recording.path.to_string_lossy());
+                                        recordings.insert(guid.to_string(), recording);
+                                    }
+                                    Err(err) => {
+                                        error!("Error creating recording of {}: {}", name, err);
+                                        failed.insert(guid.to_string(), Instant::now());
+                                    }
+                                }
+                            }
+                            let result = recordings
+                                .get_mut(guid)
+                                .map(|recording| recording.write(&pck.audio));
+                            if let Some(Err(err)) = result {
+                                if let Some(recording) = recordings.remove(guid) {
+                                    error!(
+                                        "Error writing recording {}: {}",
+                                        recording.path.to_string_lossy(),
+                                        err
+                                    );
+                                }
+                                failed.insert(guid.to_string(), Instant::now());
+                            }
+                        }
+                    }
+                }
+                // e.g. the echoed UDP pings
+                Err(err) => debug!("Ignoring invalid voice packet: {}", err),
+            }
+        }
+
+        let ended: Vec<String> = recordings
+            .iter()
+            .filter(|(_, recording)| recording.last_packet.elapsed() >= timeout)
+            .map(|(guid, _)| guid.clone())
+            .collect();
+        for guid in ended {
+            if let Some(recording) = recordings.remove(&guid) {
+                recording.finish();
+            }
+        }
+        failed.retain(|_, last_packet| last_packet.elapsed() < timeout);
+
+        if ctx.should_stop() {
+            for (_, recording) in recordings.drain() {
+                recording.finish();
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// A single transmission that is written into an Ogg/Opus file.
+struct Recording {
+    path: PathBuf,
+    writer: OggOpusWriter<BufWriter<File>>,
+    last_packet: Instant,
+}
+
+impl Recording {
+    /// Creates the file `{timestamp}_{freq}_{name}_{guid}.ogg` inside `dir`. Existing files are
+    /// never overwritten: if the file exists (e.g. the sender started another transmission within
+    /// the same second), a counter is appended to the file name.
+    fn create(dir: &Path, freq: u64, guid: &str, name: &str) -> Result<Self, Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let stem = format!(
+            "{}_{}_{}_{}",
+            timestamp,
+            freq,
+            file_name_part(name),
+            file_name_part(guid)
+        );
+
+        let mut path = dir.join(format!("{}.ogg", stem));
+        let mut n = 1;
+        let file = loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break file,
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    path = dir.join(format!("{}_{}.ogg", stem, n));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+        let writer = OggOpusWriter::new(BufWriter::new(file), 48_000, 0)?;
+
+        Ok(Recording {
+            path,
+            writer,
+            last_packet: Instant::now(),
+        })
+    }
+
+    fn write(&mut self, audio: &[u8]) -> Result<(), Error> {
+        self.writer.write(audio)?;
+        self.last_packet = Instant::now();
+
+        Ok(())
+    }
+
+    /// Finishes the file, logging (instead of returning) errors, since they only affect this
+    /// recording.
+    fn finish(self) {
+        let result = self
+            .writer
+            .finish(None)
+            .and_then(|mut file| file.flush().map_err(Error::from));
+        match result {
+            Ok(()) => debug!("Finished recording {}", self.path.to_string_lossy()),
+            Err(err) => error!(
+                "Error finishing recording {}: {}",
+                self.path.to_string_lossy(),
+                err
+            ),
+        }
+    }
+}
+
+/// Replaces all characters of a sender's name or GUID that might not be allowed in file names.
+fn file_name_part(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::Version;
+use drs_protocol::{Client, Coalition, Message, Modulation, MsgType, Position};
+
+/// Frequencies (in Hz) that are closer than this are considered to be the same frequency.
+pub(crate) const FREQ_TOLERANCE: f64 = 500.0;
+
+/// A client that is connected to the SRS server.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConnectedClient {
+    pub guid: String,
+    pub name: String,
+    pub coalition: Coalition,
+    pub position: Position,
+    pub radios: Vec<ClientRadio>,
+}
+
+/// A radio of a client that is connected to the SRS server.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientRadio {
+    pub name: String,
+    pub freq: f64,
+    /// The secondary (guard) frequency; `0.0` if none
+    pub sec_freq: f64,
+    /// The raw SRS modulation value (see `Modulation` for the known values)
+    pub modulation: u8,
+    pub enc: bool,
+    pub enc_key: u8,
+}
+
+/// The view of the SRS server (connected clients and server settings), as received through the
+/// sync connection.
+#[derive(Debug, Default)]
+pub struct ServerState {
+    own_guid: String,
+    clients: HashMap<String, ConnectedClient>,
+    settings: HashMap<String, String>,
+    version: Option<Version>,
+}
+
+impl ServerState {
+    pub fn new(own_guid: &str) -> Self {
+        ServerState {
+            own_guid: own_guid.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn clients(&self) -> Vec<ConnectedClient> {
+        self.clients.values().cloned().collect()
+    }
+
+    pub fn client(&self, guid: &str) -> Option<&ConnectedClient> {
+        self.clients.get(guid)
+    }
+
+    pub fn settings(&self) -> HashMap<String, String> {
+        self.settings.clone()
+    }
+
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Returns whether any client has a radio tuned to the given frequency and modulation.
+    pub fn has_listener(&self, freq: f64, modulation: Modulation) -> bool {
+        self.clients.values().any(|client| {
+            client.radios.iter().any(|radio| {
+                radio.modulation == modulation as u8
+                    && ((radio.freq - freq).abs() < FREQ_TOLERANCE
+                        || (radio.sec_freq - freq).abs() < FREQ_TOLERANCE)
+            })
+        })
+    }
+
+    /// Forgets all clients, e.g. after the connection to the server got lost.
+    pub fn clear(&mut self) {
+        self.clients.clear();
+    }
+
+    /// Updates the state with a message received from the server.
+    pub fn handle(&mut self, msg: Message<'_>) {
+        if let Some(settings) = msg.server_settings {
+            self.settings = settings;
+        }
+
+        match msg.msg_type {
+            MsgType::Sync => {
+                if let Ok(version) = Version::from_str(&msg.version) {
+                    self.version = Some(version);
+                }
+                if let Some(clients) = msg.clients {
+                    self.clients.clear();
+                    for client in clients {
+                        self.update_client(client);
+                    }
+                }
+            }
+            MsgType::Update | MsgType::RadioUpdate => {
+                if let Some(client) = msg.client {
+                    self.update_client(client);
+                }
+            }
+            MsgType::ClientDisconnect => {
+                if let Some(client) = msg.client {
+                    self.clients.remove(client.client_guid.as_ref());
+                }
+            }
+            MsgType::VersionMismatch => {
+                error!("SRS server reported a version mismatch (server version: {})", msg.version);
+            }
+            _ => {}
+        }
+    }
+
+    fn update_client(&mut self, client: Client<'_>) {
+        if client.client_guid.is_empty() || client.client_guid == self.own_guid {
+            return;
+        }
+
+        let radios = client.radio_info.map(|info| {
+            info.radios
+                .into_iter()
+                .map(|radio| ClientRadio {
+                    name: radio.name.into_owned(),
+                    freq: radio.freq,
+                    sec_freq: radio.sec_freq,
+                    modulation: radio.modulation,
+                    enc: radio.enc,
+                    enc_key: radio.enc_key,
+                })
+                .collect()
+        });
+
+        let guid = client.client_guid.into_owned();
+        let entry = self
+            .clients
+            .entry(guid.clone())
+            .or_insert_with(|| ConnectedClient {
+                guid,
+                name: String::new(),
+                coalition: Coalition::Spectator,
+                position: Position::default(),
+                radios: Vec::new(),
+            });
+        entry.name = client.name.into_owned();
+        entry.coalition = client.coalition;
+        entry.position = client.position;
+        // metadata updates do not contain radio information, keep the previously received radios
+        // in this case
+        if let Some(radios) = radios {
+            entry.radios = radios;
+        }
+    }
+}
@@ -0,0 +1,559 @@
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::opus::{OggOpusPacket, OggOpusReader, OggOpusWriter, OpusHead};
+use crate::{AudioFormat, Error};
+use audiopus::coder::Encoder;
+use audiopus::packet::Packet;
+use audiopus::{Application, Bitrate, Channels, MutSignals, SampleRate};
+use lewton::inside_ogg::OggStreamReader;
+use uuid::Uuid;
+
+/// Files are transcoded into mono Opus at 16 kHz (wideband, plenty for radio audio) in 20ms frames
+/// at this bitrate.
+const SAMPLE_RATE: u32 = 16_000;
+const FRAME_SAMPLES: usize = 320;
+const BITRATE: i32 = 32_000;
+
+/// The size of the buffer Opus packets are encoded into, as recommended by the libopus
+/// documentation of `opus_encode` (the bitrate keeps the actual packets far smaller).
+const MAX_PACKET_LENGTH: usize = 4000;
+
+/// The number of samples (per channel) read at once from WAV and FLAC files.
+const BLOCK_SAMPLES: usize = 4096;
+
+/// The maximum number of samples (per channel, at 48 kHz) of a single Opus packet (120ms).
+const MAX_OPUS_PACKET_SAMPLES: usize = 5760;
+
+/// Decodes the given audio file and encodes it into an Ogg/Opus file at `dst` that can be played
+/// over SRS (mono, 16 kHz, 20ms frames). Ogg/Opus files are re-encoded as well, e.g. to turn stereo
+/// files into mono ones.
+pub fn transcode<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<(), Error> {
+    let src = src.as_ref();
+    let format = AudioFormat::detect(src)?.ok_or_else(|| Error::UnsupportedFormat(src.into()))?;
+    let decoder = Decoder::open(src, format)?;
+
+    // write into a temporary file first to never leave a partially written file at `dst` behind;
+    // the file name is unique, so that multiple stations can transcode the same file at once
+    let tmp = dst
+        .as_ref()
+        .with_extension(format!("ogg.{}.part", Uuid::new_v4().simple()));
+    let result = File::create(&tmp)
+        .map_err(Error::from)
+        .and_then(|file| encode(src, decoder, BufWriter::new(file)));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(&tmp, dst)?;
+
+    Ok(())
+}
+
+/// Encodes the audio of the given decoder into an Ogg/Opus stream written to `out`.
+fn encode<W: Write>(src: &Path, mut decoder: Decoder, out: W) -> Result<(), Error> {
+    let mut encoder = Encoder::new(SampleRate::Hz16000, Channels::Mono, Application::Audio)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(BITRATE))?;
+    // the encoder's delay (in samples at 16 kHz), which the decoder has to skip (at 48 kHz)
+    let pre_skip = encoder.lookahead()? * (48_000 / SAMPLE_RATE);
+    let mut writer = OggOpusWriter::new(out, SAMPLE_RATE, pre_skip as u16)?;
+
+    let mut resampler: Option<Resampler> = None;
+    let mut frame = Vec::with_capacity(FRAME_SAMPLES * 2);
+    let mut packet = [0; MAX_PACKET_LENGTH];
+    let mut samples: u64 = 0;
+    while let Some(block) = decoder.next_block()? {
+        if block.channels == 0 || block.sample_rate == 0 {
+            return Err(Error::UnsupportedFormat(src.into()));
+        }
+
+        let mono = downmix(&block.samples, block.channels);
+        match resampler {
+            Some(ref r) if r.from == block.sample_rate => {}
+            _ => resampler = Some(Resampler::new(block.sample_rate, SAMPLE_RATE)),
+        }
+        if let Some(ref mut resampler) = resampler {
+            resampler.process(&mono, &mut frame);
+        }
+
+        let mut offset = 0;
+        while frame.len() - offset >= FRAME_SAMPLES {
+            let len = encoder.encode(&frame[offset..offset + FRAME_SAMPLES], &mut packet)?;
+            writer.write(&packet[..len])?;
+            offset += FRAME_SAMPLES;
+            samples += FRAME_SAMPLES as u64;
+        }
+        frame.drain(..offset);
+    }
+
+    // pad the last frame with silence
+    if !frame.is_empty() {
+        samples += frame.len() as u64;
+        frame.resize(FRAME_SAMPLES, 0);
+        let len = encoder.encode(&frame, &mut packet)?;
+        writer.write(&packet[..len])?;
+    }
+
+    // strip the padding (granule positions are always counted at 48 kHz)
+    let end_granule = u64::from(pre_skip) + samples * u64::from(48_000 / SAMPLE_RATE);
+    writer.finish(Some(end_granule))?.flush()?;
+
+    Ok(())
+}
+
+/// Returns the path of the transcoded version of the given audio file inside `cache_dir`, and
+/// transcodes the file first if it is not cached yet (or changed since it was cached).
+///
+/// The cached file is named `{stem}-{path hash}-{version hash}.ogg`, where the path hash is
+/// calculated over the canonical path of the file and the version hash additionally over its
+/// length and modification time. Both use FNV-1a, which (unlike `DefaultHasher`) does not change
+/// between Rust releases, so that the cache survives updates. Once a changed file got transcoded,
+/// the outdated versions of it are removed from the cache. Files of audio files that were deleted
+/// are not removed, but the whole cache directory can be deleted at any time (e.g. while no
+/// station is running), its files are transcoded again when they are played the next time.
+pub fn cached<P: AsRef<Path>, Q: AsRef<Path>>(src: P, cache_dir: Q) -> Result<PathBuf, Error> {
+    let src = src.as_ref();
+    let cache_dir = cache_dir.as_ref();
+    let meta = fs::metadata(src)?;
+
+    let path = fs::canonicalize(src)?;
+    let path = path.to_string_lossy();
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let path_hash = fnv1a(&[path.as_bytes()]);
+    let version_hash = fnv1a(&[
+        path.as_bytes(),
+        &meta.len().to_le_bytes(),
+        &modified.as_secs().to_le_bytes(),
+        &modified.subsec_nanos().to_le_bytes(),
+    ]);
+    let stem = src
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let prefix = format!("{}-{:016x}-", stem, path_hash);
+    let dst = cache_dir.join(format!("{}{:016x}.ogg", prefix, version_hash));
+
+    if !dst.exists() {
+        fs::create_dir_all(cache_dir)?;
+        info!("Transcoding {} to Opus ...", src.to_string_lossy());
+        transcode(src, &dst)?;
+        remove_outdated(cache_dir, &prefix, &dst);
+    }
+
+    Ok(dst)
+}
+
+/// Removes all files of the cache directory whose name starts with `prefix` (i.e. that are
+/// versions of the same audio file), except for `current`.
+fn remove_outdated(cache_dir: &Path, prefix: &str, current: &Path) {
+    let entries = match fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Error reading cache directory: {}", err);
+            return;
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path == current || !entry.file_name().to_string_lossy().starts_with(prefix) {
+            continue;
+        }
+
+        debug!("Removing outdated cache file {}", path.to_string_lossy());
+        if let Err(err) = fs::remove_file(&path) {
+            warn!(
+                "Error removing outdated cache file {}: {}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+/// Calculates the 64 bit FNV-1a hash of the given byte slices (as if they were concatenated).
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// A block of decoded (interleaved) 16 bit samples.
+struct Block {
+    sample_rate: u32,
+    channels: usize,
+    samples: Vec<i16>,
+}
+
+enum Decoder {
+    Opus(Box<OpusDecoder>),
+    Mp3(minimp3::Decoder<BufReader<File>>),
+    Wav(hound::WavReader<BufReader<File>>),
+    Flac(claxon::FlacReader<File>),
+    Vorbis(Box<OggStreamReader<BufReader<File>>>),
+}
+
+impl Decoder {
+    fn open(path: &Path, format: AudioFormat) -> Result<Self, Error> {
+        Ok(match format {
+            AudioFormat::Mp3 => {
+                Decoder::Mp3(minimp3::Decoder::new(BufReader::new(File::open(path)?)))
+            }
+            AudioFormat::Wav => Decoder::Wav(hound::WavReader::open(path)?),
+            AudioFormat::Flac => Decoder::Flac(claxon::FlacReader::open(path)?),
+            AudioFormat::Vorbis => Decoder::Vorbis(Box::new(OggStreamReader::new(
+                BufReader::new(File::open(path)?),
+            )?)),
+            AudioFormat::Opus => Decoder::Opus(Box::new(OpusDecoder::open(path)?)),
+        })
+    }
+
+    /// Returns the next block of decoded samples, or `None` at the end of the file.
+    fn next_block(&mut self) -> Result<Option<Block>, Error> {
+        match self {
+            Decoder::Opus(decoder) => decoder.next_block(),
+            Decoder::Mp3(decoder) => loop {
+                match decoder.next_frame() {
+                    Ok(frame) => {
+                        return Ok(Some(Block {
+                            sample_rate: frame.sample_rate as u32,
+                            channels: frame.channels,
+                            samples: frame.data,
+                        }))
+                    }
+                    // e.g. ID3 tags
+                    Err(minimp3::Error::SkippedData) => continue,
+                    Err(minimp3::Error::Eof) => return Ok(None),
+                    Err(err) => return Err(err.into()),
+                }
+            },
+            Decoder::Wav(reader) => {
+                let spec = reader.spec();
+                let channels = usize::from(spec.channels);
+                let len = BLOCK_SAMPLES * channels;
+                let samples = match spec.sample_format {
+                    hound::SampleFormat::Float => reader
+                        .samples::<f32>()
+                        .take(len)
+                        .map(|s| s.map(|s| (s.clamp(-1.0, 1.0) * 32767.0) as i16))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    hound::SampleFormat::Int if spec.bits_per_sample <= 16 => reader
+                        .samples::<i16>()
+                        .take(len)
+                        .map(|s| s.map(|s| s << (16 - spec.bits_per_sample)))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    hound::SampleFormat::Int => reader
+                        .samples::<i32>()
+                        .take(len)
+                        .map(|s| s.map(|s| (s >> (spec.bits_per_sample - 16)) as i16))
+                        .collect::<Result<Vec<_>, _>>()?,
+                };
+                if samples.is_empty() {
+                    return Ok(None);
+                }
+
+                Ok(Some(Block {
+                    sample_rate: spec.sample_rate,
+                    channels,
+                    samples,
+                }))
+            }
+            Decoder::Flac(reader) => {
+                let info = reader.streaminfo();
+                let block = match reader.blocks().read_next_or_eof(Vec::new())? {
+                    Some(block) => block,
+                    None => return Ok(None),
+                };
+
+                let mut samples =
+                    Vec::with_capacity((block.duration() * block.channels()) as usize);
+                for i in 0..block.duration() {
+                    for ch in 0..block.channels() {
+                        let sample = block.sample(ch, i);
+                        samples.push(if info.bits_per_sample > 16 {
+                            (sample >> (info.bits_per_sample - 16)) as i16
+                        } else {
+                            (sample << (16 - info.bits_per_sample)) as i16
+                        });
+                    }
+                }
+
+                Ok(Some(Block {
+                    sample_rate: info.sample_rate,
+                    channels: block.channels() as usize,
+                    samples,
+                }))
+            }
+            Decoder::Vorbis(reader) => loop {
+                match reader.read_dec_packet_itl()? {
+                    Some(samples) if samples.is_empty() => continue,
+                    Some(samples) => {
+                        return Ok(Some(Block {
+                            sample_rate: reader.ident_hdr.audio_sample_rate,
+                            channels: usize::from(reader.ident_hdr.audio_channels),
+                            samples,
+                        }))
+                    }
+                    None => return Ok(None),
+                }
+            },
+        }
+    }
+}
+
+/// Decodes the Opus streams of an Ogg file (one after another for chained streams).
+struct OpusDecoder {
+    path: PathBuf,
+    reader: OggOpusReader<BufReader<File>>,
+    decoder: audiopus::coder::Decoder,
+    channels: usize,
+    /// The number of samples (per channel) still to discard from the start of the stream.
+    pre_skip: usize,
+    buf: Vec<i16>,
+}
+
+impl OpusDecoder {
+    fn open(path: &Path) -> Result<Self, Error> {
+        let mut reader = OggOpusReader::new(BufReader::new(File::open(path)?));
+        let head = match reader.read_packet()? {
+            Some(OggOpusPacket::Head(head)) => head,
+            _ => return Err(Error::UnsupportedFormat(path.into())),
+        };
+
+        let (decoder, channels) = stream_decoder(path, &head)?;
+
+        Ok(OpusDecoder {
+            path: path.into(),
+            reader,
+            decoder,
+            channels,
+            pre_skip: usize::from(head.pre_skip),
+            buf: vec![0; MAX_OPUS_PACKET_SAMPLES * channels],
+        })
+    }
+
+    fn next_block(&mut self) -> Result<Option<Block>, Error> {
+        loop {
+            let pck = match self.reader.read_packet()? {
+                Some(OggOpusPacket::Audio(pck)) => pck,
+                // the next stream of a chained file
+                Some(OggOpusPacket::Head(head)) => {
+                    let (decoder, channels) = stream_decoder(&self.path, &head)?;
+                    self.decoder = decoder;
+                    self.channels = channels;
+                    self.pre_skip = usize::from(head.pre_skip);
+                    self.buf = vec![0; MAX_OPUS_PACKET_SAMPLES * channels];
+                    continue;
+                }
+                Some(OggOpusPacket::Tags(_)) => continue,
+                None => return Ok(None),
+            };
+            if pck.data.is_empty() {
+                continue;
+            }
+
+            let len = self.decoder.decode(
+                Some(Packet::try_from(&pck.data[..])?),
+                MutSignals::try_from(&mut self.buf[..])?,
+                false,
+            )?;
+            let skip = self.pre_skip.min(len);
+            self.pre_skip -= skip;
+            if skip == len {
+                continue;
+            }
+
+            return Ok(Some(Block {
+                sample_rate: 48_000,
+                channels: self.channels,
+                samples: self.buf[skip * self.channels..len * self.channels].to_vec(),
+            }));
+        }
+    }
+}
+
+/// Creates a decoder for the Opus stream with the given ID header, returning it along with the
+/// stream's channel count.
+fn stream_decoder(
+    path: &Path,
+    head: &OpusHead,
+) -> Result<(audiopus::coder::Decoder, usize), Error> {
+    let (channels, channels_count) = match head.channels {
+        1 => (Channels::Mono, 1),
+        2 => (Channels::Stereo, 2),
+        // multichannel streams (channel mapping family 1 and above)
+        _ => return Err(Error::UnsupportedFormat(path.into())),
+    };
+
+    Ok((
+        audiopus::coder::Decoder::new(SampleRate::Hz48000, channels)?,
+        channels_count,
+    ))
+}
+
+/// Mixes the given interleaved samples down to a single channel.
+fn downmix(samples: &[i16], channels: usize) -> Vec<i16> {
+    if channels == 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks(channels)
+        .map(|frame| {
+            let sum: i32 = frame.iter().map(|s| i32::from(*s)).sum();
+            (sum / frame.len() as i32) as i16
+        })
+        .collect()
+}
+
+/// A (streaming) linear interpolation resampler, which is good enough for radio audio. When
+/// downsampling, the input is low-pass filtered first, so that frequencies above the Nyquist
+/// frequency of the target sample rate are not aliased into the audible range.
+struct Resampler {
+    from: u32,
+    /// The distance between two output samples, in input samples.
+    step: f64,
+    /// The position of the next output sample, relative to `last`.
+    pos: f64,
+    /// The last input sample of the previous call to `process`.
+    last: Option<i16>,
+    low_pass: Option<LowPass>,
+}
+
+impl Resampler {
+    fn new(from: u32, to: u32) -> Self {
+        Resampler {
+            from,
+            step: f64::from(from) / f64::from(to),
+            pos: 0.0,
+            last: None,
+            low_pass: if from > to {
+                Some(LowPass::new(from, to))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Resamples the given samples and appends the result to `out`.
+    fn process(&mut self, samples: &[i16], out: &mut Vec<i16>) {
+        if samples.is_empty() {
+            return;
+        }
+
+        let filtered;
+        let samples = match self.low_pass {
+            Some(ref mut low_pass) => {
+                filtered = low_pass.process(samples);
+                &filtered[..]
+            }
+            None => samples,
+        };
+
+        let input: Vec<i16> = self
+            .last
+            .iter()
+            .cloned()
+            .chain(samples.iter().cloned())
+            .collect();
+        while self.pos + 1.0 < input.len() as f64 {
+            let i = self.pos as usize;
+            let frac = self.pos - i as f64;
+            let a = f64::from(input[i]);
+            let b = f64::from(input[i + 1]);
+            out.push((a + (b - a) * frac).round() as i16);
+            self.pos += self.step;
+        }
+
+        self.pos -= (input.len() - 1) as f64;
+        self.last = input.last().cloned();
+    }
+}
+
+/// The number of taps of the anti-aliasing filter per integer step of the downsampling ratio
+/// (e.g. 97 taps from 48 kHz to 16 kHz).
+const LOW_PASS_TAPS: usize = 32;
+
+/// The cutoff frequency of the anti-aliasing filter relative to the target sample rate, which
+/// leaves room for the transition band below the Nyquist frequency (0.5).
+const LOW_PASS_CUTOFF: f64 = 0.4;
+
+/// A (streaming) windowed-sinc FIR low-pass filter (Blackman window) that removes everything that
+/// cannot be represented at the target sample rate of a `Resampler`. It delays the signal by half
+/// its length (about 1ms).
+struct LowPass {
+    taps: Vec<f64>,
+    /// The last `taps.len() - 1` input samples of the previous call to `process`.
+    history: Vec<f64>,
+}
+
+impl LowPass {
+    fn new(from: u32, to: u32) -> Self {
+        let ratio = (f64::from(from) / f64::from(to)).ceil() as usize;
+        let len = LOW_PASS_TAPS * ratio + 1;
+        // the cutoff frequency relative to the input sample rate
+        let cutoff = LOW_PASS_CUTOFF * f64::from(to) / f64::from(from);
+        let center = (len - 1) as f64 / 2.0;
+
+        let mut taps: Vec<f64> = (0..len)
+            .map(|i| {
+                let x = i as f64 - center;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * x).sin() / (PI * x)
+                };
+                let phase = 2.0 * PI * i as f64 / (len - 1) as f64;
+                let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                sinc * window
+            })
+            .collect();
+        // normalize to unity gain for the frequencies that pass
+        let sum: f64 = taps.iter().sum();
+        for tap in &mut taps {
+            *tap /= sum;
+        }
+
+        LowPass {
+            history: vec![0.0; len - 1],
+            taps,
+        }
+    }
+
+    fn process(&mut self, samples: &[i16]) -> Vec<i16> {
+        let input: Vec<f64> = self
+            .history
+            .iter()
+            .cloned()
+            .chain(samples.iter().map(|s| f64::from(*s)))
+            .collect();
+        let out = input
+            .windows(self.taps.len())
+            .map(|window| {
+                let sum: f64 = window
+                    .iter()
+                    .rev()
+                    .zip(&self.taps)
+                    .map(|(s, tap)| s * tap)
+                    .sum();
+                sum.round().clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16
+            })
+            .collect();
+
+        self.history = input[input.len() - self.history.len()..].to_vec();
+        out
+    }
+}
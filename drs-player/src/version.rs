@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// A SRS version (e.g. `1.6.0.0`), which is used as the client version sent to the SRS server.
+/// The fields of the messages sent depend on it (see `has_radio_info_position`,
+/// `has_lat_lng_position` and `has_seat`). Whether the server accepts the version is up to the
+/// server, which replies with a version mismatch message otherwise.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build: u16,
+}
+
+impl Version {
+    pub fn new(major: u16, minor: u16, patch: u16, build: u16) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+            build,
+        }
+    }
+
+    /// Starting with SRS 1.9, the position is only sent as part of the client and not as part of
+    /// the radio info anymore.
+    pub(crate) fn has_radio_info_position(&self) -> bool {
+        *self < Version::new(1, 9, 0, 0)
+    }
+
+    /// Starting with SRS 1.9, the client contains its position as latitude and longitude
+    /// (`LatLngPosition`), which replaces the position of the radio info.
+    pub(crate) fn has_lat_lng_position(&self) -> bool {
+        !self.has_radio_info_position()
+    }
+
+    /// Starting with SRS 2.0, the client contains the seat it occupies in a multi-crew aircraft
+    /// and whether the server may record its transmissions.
+    pub(crate) fn has_seat(&self) -> bool {
+        *self >= Version::new(2, 0, 0, 0)
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Version::new(1, 6, 0, 0)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.patch, self.build
+        )
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // missing parts are treated as 0, e.g. `1.6` equals `1.6.0.0`
+        let mut parts = [0; 4];
+        for (i, part) in s.trim().split('.').enumerate() {
+            if i >= parts.len() {
+                return Err(Error::InvalidVersion(s.to_string()));
+            }
+            parts[i] =
+                u16::from_str(part).map_err(|_| Error::InvalidVersion(s.to_string()))?;
+        }
+
+        Ok(Version::new(parts[0], parts[1], parts[2], parts[3]))
+    }
+}
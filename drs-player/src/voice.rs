@@ -0,0 +1,205 @@
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+use drs_protocol::VoicePacket;
+
+/// SRS expects clients using UDP to regularly send a ping (containing only their GUID) to keep
+/// their voice endpoint registered.
+const UDP_PING_INTERVAL_SECS: u64 = 15;
+
+/// The largest possible voice packet (its length is sent as `u16`).
+const MAX_PACKET_LENGTH: usize = 65_535;
+
+/// The transport used to send voice packets to SRS.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VoiceTransport {
+    /// Voice over TCP, used by older SRS servers (by default on the port following the sync port).
+    Tcp,
+    /// Voice over UDP, used by newer SRS servers (by default on the same port as the sync port).
+    Udp,
+}
+
+impl Default for VoiceTransport {
+    fn default() -> Self {
+        VoiceTransport::Tcp
+    }
+}
+
+impl FromStr for VoiceTransport {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tcp" => Ok(VoiceTransport::Tcp),
+            "udp" => Ok(VoiceTransport::Udp),
+            _ => Err(Error::InvalidVoiceTransport(s.to_string())),
+        }
+    }
+}
+
+/// A UDP socket connected to the SRS voice port, which can be shared by multiple players (see
+/// `StationManager`). SRS reads the sender's GUID from each voice packet and each player registers
+/// its GUID for the socket's endpoint with its own pings, so one socket can serve every station
+/// connected to the same server.
+#[derive(Clone)]
+pub struct VoiceSocket(Arc<UdpSocket>);
+
+impl VoiceSocket {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self, Error> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "could not resolve SRS voice address")
+        })?;
+        let socket = match addr {
+            SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0")?,
+            SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+        };
+        socket.connect(addr)?;
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+        Ok(VoiceSocket(Arc::new(socket)))
+    }
+}
+
+/// A connection to the SRS voice port.
+pub enum VoiceConnection {
+    Tcp {
+        stream: TcpStream,
+        /// Received data that does not form a complete voice packet yet.
+        buf: Vec<u8>,
+    },
+    Udp {
+        socket: VoiceSocket,
+        sguid: String,
+        last_ping: Instant,
+    },
+}
+
+impl VoiceConnection {
+    pub fn connect<A: ToSocketAddrs>(
+        transport: VoiceTransport,
+        addr: A,
+        sguid: &str,
+    ) -> Result<Self, Error> {
+        match transport {
+            VoiceTransport::Tcp => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+                Ok(VoiceConnection::Tcp {
+                    stream,
+                    buf: Vec::new(),
+                })
+            }
+            VoiceTransport::Udp => VoiceConnection::shared(&VoiceSocket::connect(addr)?, sguid),
+        }
+    }
+
+    /// Sends voice packets through the given (potentially shared) UDP socket.
+    pub fn shared(socket: &VoiceSocket, sguid: &str) -> Result<Self, Error> {
+        let mut conn = VoiceConnection::Udp {
+            socket: socket.clone(),
+            sguid: sguid.to_string(),
+            last_ping: Instant::now(),
+        };
+        // register the voice endpoint right away
+        conn.ping()?;
+        Ok(conn)
+    }
+
+    /// Sends the given voice packet to SRS.
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), io::Error> {
+        match self {
+            VoiceConnection::Tcp { stream, .. } => stream.write_all(frame),
+            VoiceConnection::Udp { socket, .. } => {
+                socket.0.send(frame)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Receives the next voice packet SRS forwarded to the player. Returns `Ok(None)` if no
+    /// complete packet was received within the read timeout. The returned data is not guaranteed
+    /// to be a valid voice packet (e.g. SRS echoes the UDP pings).
+    pub fn recv(&mut self) -> Result<Option<Vec<u8>>, io::Error> {
+        match self {
+            VoiceConnection::Tcp { stream, buf } => loop {
+                let len = VoicePacket::peek_length(buf)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+                if let Some(len) = len {
+                    if buf.len() >= len {
+                        let rest = buf.split_off(len);
+                        return Ok(Some(mem::replace(buf, rest)));
+                    }
+                }
+
+                let mut chunk = [0; 4096];
+                match stream.read(&mut chunk) {
+                    Ok(0) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "connection closed by SRS",
+                        ))
+                    }
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    Err(ref err)
+                        if err.kind() == io::ErrorKind::TimedOut
+                            || err.kind() == io::ErrorKind::WouldBlock =>
+                    {
+                        return Ok(None)
+                    }
+                    Err(err) => return Err(err),
+                }
+            },
+            VoiceConnection::Udp { socket, .. } => {
+                let mut data = vec![0; MAX_PACKET_LENGTH];
+                match socket.0.recv(&mut data) {
+                    Ok(n) => {
+                        data.truncate(n);
+                        Ok(Some(data))
+                    }
+                    Err(ref err)
+                        if err.kind() == io::ErrorKind::TimedOut
+                            || err.kind() == io::ErrorKind::WouldBlock =>
+                    {
+                        Ok(None)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+        }
+    }
+
+    /// Sends the UDP keep-alive ping if it is due (no-op for TCP connections).
+    pub fn keep_alive(&mut self) -> Result<(), io::Error> {
+        let ping_due = match self {
+            VoiceConnection::Tcp { .. } => false,
+            VoiceConnection::Udp { last_ping, .. } => {
+                last_ping.elapsed() >= Duration::from_secs(UDP_PING_INTERVAL_SECS)
+            }
+        };
+        if ping_due {
+            self.ping()?;
+        }
+
+        Ok(())
+    }
+
+    fn ping(&mut self) -> Result<(), io::Error> {
+        if let VoiceConnection::Udp {
+            socket,
+            sguid,
+            last_ping,
+        } = self
+        {
+            socket.0.send(sguid.as_bytes())?;
+            *last_ping = Instant::now();
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::sync::mpsc::{channel, Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
@@ -9,6 +10,8 @@ pub struct Worker<T> {
 
 pub struct Context {
     rx: Receiver<Command>,
+    /// Whether the worker has been paused since `was_paused` was last called.
+    paused: Cell<bool>,
 }
 
 enum Command {
@@ -27,7 +30,12 @@ impl<T> Worker<T> {
         let (tx, rx) = channel();
         Worker {
             tx,
-            join_handle: thread::spawn(|| f(Context { rx })),
+            join_handle: thread::spawn(|| {
+                f(Context {
+                    rx,
+                    paused: Cell::new(false),
+                })
+            }),
         }
     }
 
@@ -78,7 +86,14 @@ impl Context {
         }
     }
 
+    /// Returns whether the worker has been paused (and unpaused again) while checking whether it
+    /// should stop since the last call.
+    pub fn was_paused(&self) -> bool {
+        self.paused.replace(false)
+    }
+
     fn pause_handler(&self) -> bool {
+        self.paused.set(true);
         loop {
             match self.rx.recv() {
                 Ok(Command::Unpause) => break,
@@ -0,0 +1,100 @@
+//! Helpers shared by the integration tests (each test crate only uses some of them).
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+/// The pre-skip (in samples at 48 kHz) of the Opus streams written by these helpers.
+pub const PRE_SKIP: u64 = 312;
+
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("drs-test-{}-{}", std::process::id(), name))
+}
+
+/// Writes an Ogg/Opus file with the given channel count, containing the given packets (each
+/// assumed to be `packet_samples` long).
+pub fn opus_file(name: &str, channels: u8, packets: &[&[u8]], packet_samples: u64) -> PathBuf {
+    let path = temp_path(name);
+    let mut writer = PacketWriter::new(File::create(&path).unwrap());
+    write_opus_stream(&mut writer, 1, channels, packets, packet_samples);
+
+    path
+}
+
+/// Returns an identification header (`OpusHead`) for a stream with the given channel count.
+pub fn opus_head(channels: u8) -> Box<[u8]> {
+    let mut head = b"OpusHead".to_vec();
+    head.extend_from_slice(&[1, channels]);
+    head.extend_from_slice(&(PRE_SKIP as u16).to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&[0, 0, 0]);
+    head.into_boxed_slice()
+}
+
+/// Returns a comment header (`OpusTags`) with the vendor `test` and the tag `TITLE=Test`.
+pub fn opus_tags() -> Box<[u8]> {
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&4u32.to_le_bytes());
+    tags.extend_from_slice(b"test");
+    tags.extend_from_slice(&1u32.to_le_bytes());
+    tags.extend_from_slice(&10u32.to_le_bytes());
+    tags.extend_from_slice(b"TITLE=Test");
+    tags.into_boxed_slice()
+}
+
+/// Writes a complete logical Opus stream with the given serial.
+pub fn write_opus_stream<W: Write>(
+    writer: &mut PacketWriter<W>,
+    serial: u32,
+    channels: u8,
+    packets: &[&[u8]],
+    packet_samples: u64,
+) {
+    let end = PacketWriteEndInfo::EndStream;
+    write_stream(writer, serial, channels, packets, packet_samples, end);
+}
+
+/// Writes a logical Opus stream with the given serial that lacks the end of stream page (like the
+/// links of truncated stream rips often do).
+pub fn write_truncated_opus_stream<W: Write>(
+    writer: &mut PacketWriter<W>,
+    serial: u32,
+    channels: u8,
+    packets: &[&[u8]],
+    packet_samples: u64,
+) {
+    let end = PacketWriteEndInfo::EndPage;
+    write_stream(writer, serial, channels, packets, packet_samples, end);
+}
+
+fn write_stream<W: Write>(
+    writer: &mut PacketWriter<W>,
+    serial: u32,
+    channels: u8,
+    packets: &[&[u8]],
+    packet_samples: u64,
+    last: PacketWriteEndInfo,
+) {
+    writer
+        .write_packet(opus_head(channels), serial, PacketWriteEndInfo::EndPage, 0)
+        .unwrap();
+    writer
+        .write_packet(opus_tags(), serial, PacketWriteEndInfo::EndPage, 0)
+        .unwrap();
+
+    for (i, packet) in packets.iter().enumerate() {
+        let end = if i + 1 == packets.len() {
+            last
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        // the granule positions include the pre-skip
+        let granule = PRE_SKIP + (i as u64 + 1) * packet_samples;
+        writer
+            .write_packet(packet.to_vec().into_boxed_slice(), serial, end, granule)
+            .unwrap();
+    }
+}
@@ -0,0 +1,919 @@
+mod common;
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use drs_protocol::{Client, MsgType, Radio, RadioInfo, VoiceFrequency, VoicePacket};
+use drsplayer::{
+    Coalition, Encryption, EncryptionMode, Error, Frequency, LatLngPosition, Modulation, Player,
+    Position, Server, StationConfig, StationManager, Version, VoiceTransport,
+};
+use mock_srs::MockServer;
+use ogg::reading::PacketReader;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+use crate::common::{opus_head, opus_tags, temp_path, write_opus_stream, PRE_SKIP};
+
+const FREQ: u64 = 251_000_000;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A 20ms CELT packet (silence).
+const OPUS_PACKET: &[u8] = &[0xf8, 0xff, 0xfe];
+
+/// Writes an Ogg/Opus file containing the given number of (silent) 20ms packets.
+fn opus_file(name: &str, packets: usize) -> PathBuf {
+    common::opus_file(name, 1, &vec![OPUS_PACKET; packets], 960)
+}
+
+fn player(srv: &MockServer, transport: VoiceTransport) -> Player {
+    let mut server = Server::new("127.0.0.1", srv.port());
+    if transport == VoiceTransport::Tcp {
+        server.set_voice_port(srv.tcp_voice_port());
+    }
+
+    let mut player = Player::new("Test", Position::default(), FREQ);
+    player.set_server(server);
+    player.set_voice_transport(transport);
+    player
+}
+
+fn sync_count(srv: &MockServer) -> usize {
+    srv.messages()
+        .iter()
+        .filter(|msg| msg.msg_type == MsgType::Sync)
+        .count()
+}
+
+fn listener(guid: &'static str) -> Client<'static> {
+    Client {
+        client_guid: Cow::Borrowed(guid),
+        name: Cow::Borrowed("Viper 1-1"),
+        radio_info: Some(RadioInfo {
+            radios: vec![Radio {
+                freq: FREQ as f64,
+                modulation: Modulation::AM as u8,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn sync_handshake() {
+    let srv = MockServer::start().unwrap();
+    srv.set_setting("COALITION_AUDIO_SECURITY", "False");
+    let path = opus_file("sync_handshake.ogg", 10);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| sync_count(srv) >= 1));
+
+    let sync = srv.messages().into_iter().next().unwrap();
+    assert_eq!(sync.msg_type, MsgType::Sync);
+    assert_eq!(sync.version, "1.6.0.0");
+    let client = sync.client.unwrap();
+    assert_eq!(client.name, "ATIS Test");
+    let radios = client.radio_info.unwrap().radios;
+    assert_eq!(radios.len(), 1);
+    assert_eq!(radios[0].freq, FREQ as f64);
+
+    // the sync is followed by an update containing the position of the station
+    assert!(srv.wait_for(TIMEOUT, |srv| {
+        srv.messages().iter().any(|msg| msg.msg_type == MsgType::Update)
+    }));
+
+    assert_eq!(handle.server_version(), Some(Version::new(1, 6, 0, 0)));
+    assert_eq!(
+        handle.server_settings().get("COALITION_AUDIO_SECURITY"),
+        Some(&"False".to_string())
+    );
+
+    handle.stop();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn undecodable_message_before_sync_reply() {
+    let srv = MockServer::start().unwrap();
+    // a message of a type unknown to this crate
+    srv.set_sync_reply_prefix(b"{\"MsgType\":42,\"Version\":\"1.6.0.0\"}\n");
+    let path = opus_file("undecodable_message_before_sync_reply.ogg", 10);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert_eq!(handle.server_version(), Some(Version::new(1, 6, 0, 0)));
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn client_fields_by_version() {
+    // version, position in the radio info, latitude and longitude, seat and allow record
+    let versions = [
+        ("1.6.0.0", true, false, false),
+        ("1.9.0.0", false, true, false),
+        ("2.0.0.0", false, true, true),
+    ];
+    for &(version, has_position, has_lat_lng, has_seat) in &versions {
+        let srv = MockServer::start().unwrap();
+        srv.set_version(version);
+        let path = opus_file("client_fields_by_version.ogg", 10);
+
+        let mut player = player(&srv, VoiceTransport::Tcp);
+        player.set_version(version.parse().unwrap());
+        player.set_lat_lng_position(LatLngPosition {
+            lat: 41.6,
+            lng: 41.6,
+            alt: 10.0,
+        });
+        let handle = player.spawn(&path, true).unwrap();
+        assert!(srv.wait_for(TIMEOUT, |srv| srv.messages().len() >= 2));
+        handle.stop();
+
+        // both the sync and the update message contain the fields of the version
+        let messages = srv.messages();
+        assert_eq!(messages[0].msg_type, MsgType::Sync);
+        assert_eq!(messages[1].msg_type, MsgType::Update);
+        for msg in messages.into_iter().take(2) {
+            assert_eq!(msg.version, version);
+            let client = msg.client.unwrap();
+            if let Some(radio_info) = client.radio_info {
+                assert_eq!(radio_info.pos.is_some(), has_position, "version {}", version);
+            }
+            match client.lat_lng_position {
+                Some(lat_lng) => {
+                    assert!(has_lat_lng, "version {}", version);
+                    // sent slightly elevated like the position
+                    assert_eq!((lat_lng.lat, lat_lng.lng), (41.6, 41.6));
+                    assert!(lat_lng.alt > 10.0);
+                }
+                None => assert!(!has_lat_lng, "version {}", version),
+            }
+            assert_eq!(client.seat.is_some(), has_seat, "version {}", version);
+            assert_eq!(client.allow_record.is_some(), has_seat, "version {}", version);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn position_updates() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("position_updates.ogg", 10);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    let position = Position {
+        x: -281_702.5,
+        y: 647_235.25,
+        alt: 20.0,
+    };
+    handle.set_position(position.clone());
+    assert_eq!(handle.position(), position);
+
+    // the new position is sent right away (elevated by 100m for LOS)
+    assert!(srv.wait_for(Duration::from_secs(1), |srv| {
+        srv.messages().iter().any(|msg| {
+            msg.msg_type == MsgType::Update
+                && msg.client.as_ref().map(|c| &c.position)
+                    == Some(&Position {
+                        alt: 120.0,
+                        ..position.clone()
+                    })
+        })
+    }));
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn retune() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("retune.ogg", 100);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| !srv.voice_packets().is_empty()));
+
+    let freq = Frequency::new(30_000_000, Modulation::FM);
+    handle.set_frequencies(vec![freq]).unwrap();
+    assert_eq!(handle.frequencies(), vec![freq]);
+    // a station cannot broadcast on no frequency at all
+    assert!(handle.set_frequencies(Vec::new()).is_err());
+    assert_eq!(handle.frequencies(), vec![freq]);
+
+    // SRS is informed about the new radio through a fresh sync
+    assert!(srv.wait_for(TIMEOUT, |srv| sync_count(srv) >= 2));
+    let sync = srv
+        .messages()
+        .into_iter()
+        .filter(|msg| msg.msg_type == MsgType::Sync)
+        .nth(1)
+        .unwrap();
+    let radios = sync.client.unwrap().radio_info.unwrap().radios;
+    assert_eq!(radios.len(), 1);
+    assert_eq!(radios[0].freq, 30_000_000.0);
+    assert_eq!(radios[0].modulation, Modulation::FM as u8);
+
+    // all subsequent packets are sent on the new frequency only
+    let retuned = VoiceFrequency {
+        freq: 30_000_000.0,
+        modulation: Modulation::FM as u8,
+        encryption: 0,
+    };
+    assert!(srv.wait_for(TIMEOUT, |srv| {
+        srv.voice_packets().last().map(|pck| &pck.frequencies) == Some(&vec![retuned])
+    }));
+    let packets = srv.voice_packets();
+    let first = packets
+        .iter()
+        .position(|pck| pck.frequencies == vec![retuned])
+        .unwrap();
+    assert!(packets[first..]
+        .iter()
+        .all(|pck| pck.frequencies == vec![retuned]));
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn broadcast_voice_packets() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("broadcast_voice_packets.ogg", 10);
+
+    // the file is played once, so wait for the playback to finish instead of a number of packets
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, false).unwrap();
+    handle.wait();
+    // the server may still be reading the last packets off the socket
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 10));
+    assert_eq!(srv.voice_packets().len(), 10);
+
+    let guid = srv.messages()[0].client.as_ref().unwrap().client_guid.clone();
+    for (i, pck) in srv.voice_packets().into_iter().enumerate() {
+        assert_eq!(pck.audio.as_ref(), OPUS_PACKET);
+        assert_eq!(
+            pck.frequencies,
+            vec![VoiceFrequency {
+                freq: FREQ as f64,
+                modulation: Modulation::AM as u8,
+                encryption: 0,
+            }]
+        );
+        assert_eq!(pck.packet_id, i as u64 + 1);
+        assert_eq!(pck.guid, guid);
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+/// Returns the coalition of the client of the first sync and the first update message.
+fn sent_coalitions(srv: &MockServer) -> (Coalition, Coalition) {
+    let messages = srv.messages();
+    let coalition = |msg_type| {
+        messages
+            .iter()
+            .find(|msg| msg.msg_type == msg_type)
+            .and_then(|msg| msg.client.as_ref())
+            .map(|client| client.coalition)
+            .unwrap()
+    };
+    (coalition(MsgType::Sync), coalition(MsgType::Update))
+}
+
+#[test]
+fn coalition() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("coalition.ogg", 10);
+
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player.set_coalition(Coalition::Red);
+    let handle = player.spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.messages().len() >= 2));
+    handle.stop();
+
+    assert_eq!(sent_coalitions(&srv), (Coalition::Red, Coalition::Red));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn station_coalition() {
+    // the coalition from the station name takes precedence over the side of the static, which
+    // is used if the name does not contain one
+    let stations = [
+        ("SRS Player 251 AM blue", Coalition::Blue),
+        ("SRS Player 251 AM", Coalition::Red),
+    ];
+    for &(name, coalition) in &stations {
+        let srv = MockServer::start().unwrap();
+        let path = opus_file("station_coalition.ogg", 10);
+
+        let mut server = Server::new("127.0.0.1", srv.port());
+        server.set_voice_port(srv.tcp_voice_port());
+        let config = StationConfig::parse(name).unwrap();
+        let mut player = config.player(Position::default(), Coalition::Red);
+        player.set_server(server);
+        let handle = player.spawn(&path, true).unwrap();
+        assert!(srv.wait_for(TIMEOUT, |srv| srv.messages().len() >= 2));
+        handle.stop();
+
+        assert_eq!(sent_coalitions(&srv), (coalition, coalition), "{}", name);
+
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn encrypted_broadcast() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("encrypted_broadcast.ogg", 10);
+
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player
+        .set_frequencies(vec![
+            Frequency {
+                encryption: Some(Encryption::new(42).unwrap()),
+                ..Frequency::new(FREQ, Modulation::AM)
+            },
+            Frequency {
+                encryption: Some(Encryption::new(42).unwrap()),
+                ..Frequency::new(30_000_000, Modulation::FM)
+            },
+        ])
+        .unwrap();
+    let handle = player.spawn(&path, false).unwrap();
+    handle.wait();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 10));
+
+    // the radios of the sync message are encrypted with the key, using the full encryption mode
+    let radios = srv.messages()[0]
+        .client
+        .as_ref()
+        .unwrap()
+        .radio_info
+        .as_ref()
+        .unwrap()
+        .radios
+        .clone();
+    assert_eq!(radios.len(), 2);
+    for radio in radios {
+        assert!(radio.enc);
+        assert_eq!(radio.enc_key, 42);
+        assert_eq!(radio.enc_mode, EncryptionMode::Full as u8);
+    }
+
+    // every frequency of every voice packet carries the key
+    for pck in srv.voice_packets() {
+        assert_eq!(pck.frequencies.len(), 2);
+        assert!(pck.frequencies.iter().all(|freq| freq.encryption == 42));
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn invalid_encryption_key() {
+    assert_eq!(Encryption::new(1).unwrap().key(), 1);
+    assert_eq!(Encryption::new(252).unwrap().key(), 252);
+    for &key in &[0, 253] {
+        match Encryption::new(key) {
+            Err(Error::InvalidEncryptionKey(k)) => assert_eq!(k, key),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("expected encryption key {} to be rejected", key),
+        }
+    }
+}
+
+#[test]
+fn no_frequencies() {
+    let position = Position {
+        x: 0.0,
+        y: 0.0,
+        alt: 0.0,
+    };
+    let mut player = Player::new("no_frequencies", position, FREQ);
+    match player.set_frequencies(Vec::new()) {
+        Err(Error::NoFrequency) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected player to reject an empty list of frequencies"),
+    }
+}
+
+#[test]
+fn pause_and_unpause() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("pause_and_unpause.ogg", 100);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, false).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 5));
+    handle.pause();
+    thread::sleep(Duration::from_millis(100));
+    let paused = srv.voice_packets().len();
+    thread::sleep(Duration::from_secs(1));
+    assert_eq!(srv.voice_packets().len(), paused);
+
+    // the playback is resumed at real-time speed, instead of catching up on the second it was
+    // paused (which would send 50 packets at once)
+    handle.unpause();
+    thread::sleep(Duration::from_millis(200));
+    let resumed = srv.voice_packets().len() - paused;
+    assert!((1..=20).contains(&resumed), "sent {} packets in 200ms", resumed);
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unplayable_playlist() {
+    let srv = MockServer::start().unwrap();
+    let path = temp_path("unplayable_playlist.wav");
+    fs::write(&path, b"not audio").unwrap();
+
+    // files are only prepared once the broadcast reaches them, so starting the player succeeds,
+    // but the looped playback ends since none of its files can be played
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |_| !handle.is_running()));
+    assert!(srv.voice_packets().is_empty());
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "transcode")]
+#[test]
+fn play_transcoded_wav() {
+    let srv = MockServer::start().unwrap();
+    let path = temp_path("play_transcoded_wav.wav");
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16_000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let write_silence = |samples: usize| {
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..samples {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    };
+    // 200ms of silence
+    write_silence(3200);
+    let cache_dir = temp_path("play_transcoded_wav_cache");
+
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player.set_cache_dir(&cache_dir);
+    let handle = player.clone().spawn(&path, false).unwrap();
+    handle.wait();
+    // the server may still be reading the last packets off the socket
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 10));
+    assert_eq!(srv.voice_packets().len(), 10);
+    assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+    // a changed file is transcoded again and replaces its outdated version in the cache
+    write_silence(6400);
+    let handle = player.spawn(&path, false).unwrap();
+    handle.wait();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 30));
+    assert_eq!(srv.voice_packets().len(), 30);
+    assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+    fs::remove_file(path).unwrap();
+    fs::remove_dir_all(cache_dir).unwrap();
+}
+
+#[cfg(feature = "transcode")]
+#[test]
+fn play_transcoded_stereo_opus() {
+    let srv = MockServer::start().unwrap();
+    // a stereo Ogg/Opus file containing 10 silent 10ms CELT packets
+    let packet: &[u8] = &[0xf4, 0xff, 0xfe];
+    let path = common::opus_file("play_transcoded_stereo_opus.ogg", 2, &[packet; 10], 480);
+    let cache_dir = temp_path("play_transcoded_stereo_opus_cache");
+
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player.set_cache_dir(&cache_dir);
+    let handle = player.spawn(&path, false).unwrap();
+    handle.wait();
+    // 100ms of audio sent as 20ms frames, some of which may still be read off the socket
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 5));
+    assert_eq!(srv.voice_packets().len(), 5);
+    assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+    for pck in srv.voice_packets() {
+        // single frame packets
+        assert_eq!(pck.audio[0] & 0x03, 0);
+    }
+
+    fs::remove_file(path).unwrap();
+    fs::remove_dir_all(cache_dir).unwrap();
+}
+
+#[test]
+fn chained_and_multiplexed_streams() {
+    let srv = MockServer::start().unwrap();
+    // two chained Opus streams (serials 1 and 2) of 5 packets each, the first one multiplexed with
+    // a video stream (serial 3) whose packets happen to look like Opus packets
+    let path = temp_path("chained_and_multiplexed_streams.ogg");
+    let mut writer = PacketWriter::new(File::create(&path).unwrap());
+    let video_head = b"\x80theora".to_vec().into_boxed_slice();
+    let video_packet = vec![0xf8, 0x01, 0x02].into_boxed_slice();
+
+    writer
+        .write_packet(opus_head(1), 1, PacketWriteEndInfo::EndPage, 0)
+        .unwrap();
+    writer
+        .write_packet(video_head, 3, PacketWriteEndInfo::EndPage, 0)
+        .unwrap();
+    writer
+        .write_packet(opus_tags(), 1, PacketWriteEndInfo::EndPage, 0)
+        .unwrap();
+    for i in 1..=5 {
+        let end = if i == 5 {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::EndPage
+        };
+        writer
+            .write_packet(OPUS_PACKET.to_vec().into_boxed_slice(), 1, end, PRE_SKIP + i * 960)
+            .unwrap();
+        writer.write_packet(video_packet.clone(), 3, end, i).unwrap();
+    }
+
+    write_opus_stream(&mut writer, 2, 1, &[OPUS_PACKET; 5], 960);
+    drop(writer);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, false).unwrap();
+    handle.wait();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 10));
+
+    // neither the header packets nor the packets of the video stream are sent
+    let packets = srv.voice_packets();
+    assert_eq!(packets.len(), 10);
+    for pck in packets {
+        assert_eq!(pck.audio.as_ref(), OPUS_PACKET);
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn udp_voice_transport() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("udp_voice_transport.ogg", 10);
+
+    let handle = player(&srv, VoiceTransport::Udp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 10));
+    assert!(srv.udp_pings() >= 1);
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn no_default_voice_port() {
+    let path = opus_file("no_default_voice_port.ogg", 10);
+
+    // there is no port following 65535 to use as TCP voice port
+    let mut player = Player::new("Test", Position::default(), FREQ);
+    player.set_server(Server::new("127.0.0.1", 65535));
+    match player.clone().spawn(&path, true) {
+        Err(Error::NoVoicePort(port)) => assert_eq!(port, 65535),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected player to fail without a voice port"),
+    }
+
+    // which is neither needed if the voice port is set, nor for UDP (nothing listens on the port,
+    // so connecting fails afterwards)
+    let mut server = Server::new("127.0.0.1", 65535);
+    server.set_voice_port(65534);
+    let mut with_voice_port = player.clone();
+    with_voice_port.set_server(server);
+    let mut udp = player;
+    udp.set_voice_transport(VoiceTransport::Udp);
+    for player in vec![with_voice_port, udp] {
+        match player.spawn(&path, true) {
+            Err(Error::NoVoicePort(_)) => panic!("expected the voice port to be known"),
+            Err(_) => {}
+            Ok(_) => panic!("expected player to fail to connect"),
+        }
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn incompatible_server_version() {
+    // the server rejects clients older than its minimum version
+    let srv = MockServer::start().unwrap();
+    srv.set_version("2.0.0.0");
+    srv.set_min_client_version("1.9.0.0");
+    let path = opus_file("incompatible_server_version.ogg", 10);
+
+    match player(&srv, VoiceTransport::Tcp).spawn(&path, true) {
+        Err(Error::IncompatibleServerVersion(client, server)) => {
+            assert_eq!(client, Version::new(1, 6, 0, 0));
+            assert_eq!(server, Version::new(2, 0, 0, 0));
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected player to reject the server version"),
+    }
+
+    // whereas an older client version of the same major version is accepted
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player.set_version(Version::new(1, 9, 0, 0));
+    let handle = player.spawn(&path, true).unwrap();
+    assert_eq!(handle.server_version(), Some(Version::new(2, 0, 0, 0)));
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn station_manager() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("station_manager.ogg", 10);
+
+    let mut manager = StationManager::new();
+    for (id, freq) in &[("b", 252_000_000), ("a", FREQ)] {
+        let mut player = player(&srv, VoiceTransport::Tcp);
+        player.set_name(id);
+        player
+            .set_frequencies(vec![Frequency::new(*freq, Modulation::AM)])
+            .unwrap();
+        manager.spawn(id, player, &path, true).unwrap();
+    }
+    assert!(srv.wait_for(TIMEOUT, |srv| sync_count(srv) >= 2));
+    assert_eq!(manager.ids(), vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(manager.get("b").unwrap().frequencies()[0].freq, 252_000_000);
+
+    let mut names: Vec<String> = srv
+        .messages()
+        .into_iter()
+        .filter(|msg| msg.msg_type == MsgType::Sync)
+        .filter_map(|msg| msg.client.map(|c| c.name.to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names, vec!["ATIS a".to_string(), "ATIS b".to_string()]);
+
+    match manager.spawn("a", player(&srv, VoiceTransport::Tcp), &path, true) {
+        Err(Error::StationExists(id)) => assert_eq!(id, "a"),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected a duplicate station id to be rejected"),
+    }
+
+    assert!(manager.stop("a"));
+    assert!(!manager.stop("a"));
+    assert_eq!(manager.ids(), vec!["b".to_string()]);
+
+    manager.stop_all();
+    assert!(manager.is_empty());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn stations_cloned_from_one_player() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("stations_cloned_from_one_player.ogg", 10);
+
+    let template = player(&srv, VoiceTransport::Tcp);
+    let mut manager = StationManager::new();
+    manager.spawn("a", template.clone(), &path, true).unwrap();
+    manager.spawn("b", template.clone(), &path, true).unwrap();
+
+    assert!(srv.wait_for(TIMEOUT, |srv| sync_count(srv) >= 2));
+    let mut guids: Vec<String> = srv
+        .messages()
+        .into_iter()
+        .filter(|msg| msg.msg_type == MsgType::Sync)
+        .filter_map(|msg| msg.client.map(|c| c.client_guid.to_string()))
+        .collect();
+    guids.sort();
+    guids.dedup();
+    assert_eq!(guids.len(), 2);
+
+    manager.stop_all();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn shared_udp_voice_socket() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("shared_udp_voice_socket.ogg", 10);
+
+    let template = player(&srv, VoiceTransport::Udp);
+    let mut manager = StationManager::new();
+    manager.spawn("a", template.clone(), &path, true).unwrap();
+    manager.spawn("b", template.clone(), &path, true).unwrap();
+
+    // each station registers its own GUID, but all packets are sent from the same endpoint
+    let guids = |srv: &MockServer| {
+        let mut guids: Vec<String> = srv
+            .voice_packets()
+            .into_iter()
+            .map(|pck| pck.guid.to_string())
+            .collect();
+        guids.sort();
+        guids.dedup();
+        guids.len()
+    };
+    assert!(srv.wait_for(TIMEOUT, |srv| guids(srv) == 2 && srv.udp_pings() >= 2));
+    assert_eq!(srv.udp_peers().len(), 1);
+
+    manager.stop_all();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn reconnect_after_disconnect() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("reconnect_after_disconnect.ogg", 100);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 5));
+
+    srv.disconnect_all();
+    let received = srv.voice_packets().len();
+
+    // the handshake is repeated and the broadcast resumes
+    assert!(srv.wait_for(TIMEOUT, |srv| sync_count(srv) >= 2));
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= received + 5));
+    assert!(handle.is_running());
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn slow_server() {
+    let srv = MockServer::start().unwrap();
+    srv.set_delay(Duration::from_millis(200));
+    let path = opus_file("slow_server.ogg", 10);
+
+    let handle = player(&srv, VoiceTransport::Tcp).spawn(&path, true).unwrap();
+    assert!(srv.wait_for(TIMEOUT, |srv| srv.voice_packets().len() >= 5));
+    assert!(handle.is_running());
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn listener_gated() {
+    let srv = MockServer::start().unwrap();
+    let path = opus_file("listener_gated.ogg", 100);
+
+    let mut player = player(&srv, VoiceTransport::Tcp);
+    player.set_listener_gated(true);
+    let handle = player.spawn(&path, true).unwrap();
+
+    thread::sleep(Duration::from_millis(500));
+    assert!(srv.voice_packets().is_empty());
+
+    srv.add_client(listener("t5kTr0bGRKuEGnAmJx5o3w"));
+    assert!(srv.wait_for(TIMEOUT, |srv| !srv.voice_packets().is_empty()));
+    assert_eq!(handle.clients().len(), 1);
+    handle.stop();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn record_transmissions() {
+    let srv = MockServer::start().unwrap();
+    let dir = temp_path("record_transmissions");
+    srv.add_client(listener("t5kTr0bGRKuEGnAmJx5o3w"));
+
+    let handle = player(&srv, VoiceTransport::Tcp).record(&dir).unwrap();
+
+    let pck = VoicePacket {
+        audio: Cow::Borrowed(OPUS_PACKET),
+        frequencies: vec![VoiceFrequency {
+            freq: FREQ as f64,
+            modulation: Modulation::AM as u8,
+            encryption: 0,
+        }],
+        unit_id: 0,
+        packet_id: 1,
+        guid: Cow::Borrowed("t5kTr0bGRKuEGnAmJx5o3w"),
+    };
+    // keep transmitting until the recording got started
+    assert!(srv.wait_for(TIMEOUT, |srv| {
+        srv.send_voice(&pck).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        fs::read_dir(&dir).unwrap().next().is_some()
+    }));
+    handle.stop();
+
+    // a slow machine might split the transmission into several recordings, each of which must
+    // be a complete Ogg/Opus file
+    let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+    assert!(!files.is_empty());
+    for file in files {
+        let path = file.unwrap().path();
+        assert!(path
+            .to_string_lossy()
+            .ends_with("_251000000_Viper_1-1_t5kTr0bGRKuEGnAmJx5o3w.ogg"));
+
+        let mut rd = PacketReader::new(File::open(&path).unwrap());
+        assert!(rd.read_packet().unwrap().unwrap().data.starts_with(b"OpusHead"));
+        assert!(rd.read_packet().unwrap().unwrap().data.starts_with(b"OpusTags"));
+        let mut last = None;
+        while let Some(pck) = rd.read_packet().unwrap() {
+            assert_eq!(pck.data, OPUS_PACKET);
+            last = Some(pck);
+        }
+        assert!(last.unwrap().last_in_stream());
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn voice_packet(guid: &'static str) -> VoicePacket<'static> {
+    VoicePacket {
+        audio: Cow::Borrowed(OPUS_PACKET),
+        frequencies: vec![VoiceFrequency {
+            freq: FREQ as f64,
+            modulation: Modulation::AM as u8,
+            encryption: 0,
+        }],
+        unit_id: 0,
+        packet_id: 1,
+        guid: Cow::Borrowed(guid),
+    }
+}
+
+#[test]
+fn record_senders_with_the_same_name() {
+    let srv = MockServer::start().unwrap();
+    let dir = temp_path("record_senders_with_the_same_name");
+    // both listeners are named `Viper 1-1`
+    srv.add_client(listener("t5kTr0bGRKuEGnAmJx5o3w"));
+    srv.add_client(listener("Zo7B0Kx2Ikm2fFrQgNcqVw"));
+
+    let handle = player(&srv, VoiceTransport::Tcp).record(&dir).unwrap();
+
+    // simultaneous transmissions are recorded into separate files instead of overwriting each
+    // other
+    let first = voice_packet("t5kTr0bGRKuEGnAmJx5o3w");
+    let second = voice_packet("Zo7B0Kx2Ikm2fFrQgNcqVw");
+    assert!(srv.wait_for(TIMEOUT, |srv| {
+        srv.send_voice(&first).unwrap();
+        srv.send_voice(&second).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        fs::read_dir(&dir).unwrap().count() >= 2
+    }));
+    handle.stop();
+
+    let files: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|file| file.unwrap().path().to_string_lossy().into_owned())
+        .collect();
+    for guid in &["t5kTr0bGRKuEGnAmJx5o3w", "Zo7B0Kx2Ikm2fFrQgNcqVw"] {
+        let suffix = format!("_251000000_Viper_1-1_{}.ogg", guid);
+        assert!(files.iter().any(|file| file.ends_with(&suffix)), "{:?}", files);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn record_after_failed_recording() {
+    let srv = MockServer::start().unwrap();
+    let dir = temp_path("record_after_failed_recording");
+    srv.add_client(listener("t5kTr0bGRKuEGnAmJx5o3w"));
+    srv.add_client(listener("Zo7B0Kx2Ikm2fFrQgNcqVw"));
+
+    let handle = player(&srv, VoiceTransport::Tcp).record(&dir).unwrap();
+
+    // the recording of the first transmission cannot be created
+    fs::remove_dir(&dir).unwrap();
+    let first = voice_packet("t5kTr0bGRKuEGnAmJx5o3w");
+    for _ in 0..10 {
+        srv.send_voice(&first).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    // which does not stop the recorder from recording the next one
+    fs::create_dir(&dir).unwrap();
+    let second = voice_packet("Zo7B0Kx2Ikm2fFrQgNcqVw");
+    assert!(srv.wait_for(TIMEOUT, |srv| {
+        srv.send_voice(&second).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        fs::read_dir(&dir).unwrap().next().is_some()
+    }));
+    assert!(handle.is_running());
+    handle.stop();
+
+    let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let path = files[0].as_ref().unwrap().path();
+    assert!(path.to_string_lossy().ends_with("_Zo7B0Kx2Ikm2fFrQgNcqVw.ogg"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use std::fs::{self, File};
+use std::time::Duration;
+
+use drsplayer::{probe, AudioFormat, Problem};
+use ogg::writing::PacketWriter;
+
+use crate::common::{opus_file, temp_path, write_opus_stream, write_truncated_opus_stream};
+
+#[test]
+fn srs_ready_opus_file() {
+    // 50 20ms CELT packets
+    let packet: &[u8] = &[0xf8, 0xff, 0xfe];
+    let path = opus_file("srs_ready_opus_file.ogg", 1, &[packet; 50], 960);
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.format, AudioFormat::Opus);
+    assert_eq!(info.channels, 1);
+    assert_eq!(info.sample_rate, 48_000);
+    assert_eq!(info.frame_durations, vec![Duration::from_millis(20)]);
+    assert_eq!(info.duration, Some(Duration::from_secs(1)));
+    assert!(info.bitrate.is_some());
+    assert_eq!(info.vendor, Some("test".to_string()));
+    assert_eq!(info.tags, vec!["TITLE=Test".to_string()]);
+    assert_eq!(info.problems, vec![]);
+    assert!(!info.needs_transcoding());
+    assert!(info.is_playable());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn oversized_opus_packets() {
+    // 20ms CELT packets, one of them too large to fit into a voice packet
+    let small: &[u8] = &[0xf8, 0xff, 0xfe];
+    let mut large = vec![0xf8];
+    large.resize(70_000, 0xff);
+    let path = opus_file(
+        "oversized_opus_packets.ogg",
+        1,
+        &[small, &large, small],
+        960,
+    );
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.frame_durations, vec![Duration::from_millis(20)]);
+    assert_eq!(info.problems, vec![Problem::PacketSize(1)]);
+    assert!(info.needs_transcoding());
+    // played after transcoding it, which requires the transcode feature
+    assert_eq!(info.is_playable(), cfg!(feature = "transcode"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn chained_opus_streams() {
+    // a mono stream of 1s followed by a stereo stream of 0.5s
+    let path = temp_path("chained_opus_streams.ogg");
+    let mut writer = PacketWriter::new(File::create(&path).unwrap());
+    let packet: &[u8] = &[0xf8, 0xff, 0xfe];
+    write_opus_stream(&mut writer, 1, 1, &[packet; 50], 960);
+    write_opus_stream(&mut writer, 2, 2, &[packet; 25], 960);
+    drop(writer);
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.channels, 1);
+    assert_eq!(info.duration, Some(Duration::from_millis(1500)));
+    assert_eq!(info.tags, vec!["TITLE=Test".to_string()]);
+    assert_eq!(info.problems, vec![Problem::Channels(2)]);
+    // the stereo stream is transcoded like any other file that is not SRS-ready
+    assert_eq!(info.is_playable(), cfg!(feature = "transcode"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn chained_opus_streams_without_end_of_stream() {
+    // a stream of 1s that lacks the end of stream page, followed by a stream of 0.5s
+    let path = temp_path("chained_opus_streams_without_end_of_stream.ogg");
+    let mut writer = PacketWriter::new(File::create(&path).unwrap());
+    let packet: &[u8] = &[0xf8, 0xff, 0xfe];
+    write_truncated_opus_stream(&mut writer, 1, 1, &[packet; 50], 960);
+    write_opus_stream(&mut writer, 2, 1, &[packet; 25], 960);
+    drop(writer);
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.duration, Some(Duration::from_millis(1500)));
+    assert_eq!(info.problems, vec![]);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn incompatible_opus_file() {
+    // stereo 10ms CELT packets, and a packet with an invalid frame count
+    let path = opus_file(
+        "incompatible_opus_file.ogg",
+        2,
+        &[&[0x94, 0xff, 0xfe], &[0x94, 0xff, 0xfe], &[0xfb, 0x00]],
+        480,
+    );
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.channels, 2);
+    assert_eq!(info.frame_durations, vec![Duration::from_millis(10)]);
+    assert_eq!(
+        info.problems,
+        vec![Problem::Channels(2), Problem::InvalidPackets(1)]
+    );
+    // played after transcoding it into a mono stream
+    assert!(info.needs_transcoding());
+    // the invalid packet remains a problem after transcoding
+    assert!(!info.is_playable());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn opus_frame_durations() {
+    // mono files with 10ms CELT, 40ms SILK and 60ms SILK packets are played as they are
+    let files: [(&str, &[u8], u64); 3] = [
+        ("opus_frame_durations_10ms.ogg", &[0xf0, 0xff, 0xfe], 480),
+        ("opus_frame_durations_40ms.ogg", &[0x10, 0xff, 0xfe], 1920),
+        ("opus_frame_durations_60ms.ogg", &[0x18, 0xff, 0xfe], 2880),
+    ];
+    for &(name, packet, samples) in &files {
+        let path = opus_file(name, 1, &[packet; 10], samples);
+
+        let info = probe(&path).unwrap();
+        assert_eq!(
+            info.frame_durations,
+            vec![Duration::from_micros(samples * 1_000_000 / 48_000)]
+        );
+        assert_eq!(info.problems, vec![]);
+        assert!(!info.needs_transcoding());
+        assert!(info.is_playable());
+
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[cfg(feature = "transcode")]
+#[test]
+fn wav_file() {
+    let path = temp_path("probe_wav_file.wav");
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44_100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for _ in 0..44_100 * 2 {
+        writer.write_sample(0i16).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let info = probe(&path).unwrap();
+    assert_eq!(info.format, AudioFormat::Wav);
+    assert_eq!(info.channels, 2);
+    assert_eq!(info.sample_rate, 44_100);
+    assert_eq!(info.bitrate, Some(1_411_200));
+    assert_eq!(info.duration, Some(Duration::from_secs(1)));
+    // transcoded before playing
+    assert_eq!(info.problems, vec![]);
+    assert!(info.needs_transcoding());
+    assert!(info.is_playable());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(not(feature = "transcode"))]
+#[test]
+fn wav_file_without_transcoding() {
+    let path = temp_path("probe_wav_file_without_transcoding.wav");
+    fs::write(&path, b"RIFF").unwrap();
+
+    match probe(&path) {
+        Err(drsplayer::Error::TranscodingDisabled(p)) => assert_eq!(p, path),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected probing a WAV file to require the transcode feature"),
+    }
+
+    fs::remove_file(path).unwrap();
+}
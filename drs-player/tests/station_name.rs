@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use drs_protocol::{Coalition, Modulation};
+use drsplayer::{Frequency, StationConfig};
+
+#[test]
+fn full_station_name() {
+    assert_eq!(
+        StationConfig::parse("SRS Player 251.000 AM blue music/"),
+        Some(StationConfig {
+            freq: Frequency::new(251_000_000, Modulation::AM),
+            coalition: Some(Coalition::Blue),
+            path: Some(PathBuf::from("music/")),
+        })
+    );
+}
+
+#[test]
+fn default_station_settings() {
+    assert_eq!(
+        StationConfig::parse("SRS Player"),
+        Some(StationConfig {
+            freq: Frequency::new(255_000_000, Modulation::AM),
+            coalition: None,
+            path: None,
+        })
+    );
+}
+
+#[test]
+fn partial_station_names() {
+    assert_eq!(
+        StationConfig::parse("SRS Player 30.5 FM"),
+        Some(StationConfig {
+            freq: Frequency::new(30_500_000, Modulation::FM),
+            coalition: None,
+            path: None,
+        })
+    );
+    assert_eq!(
+        StationConfig::parse("srs player red atis.ogg"),
+        Some(StationConfig {
+            freq: Frequency::new(255_000_000, Modulation::AM),
+            coalition: Some(Coalition::Red),
+            path: Some(PathBuf::from("atis.ogg")),
+        })
+    );
+    assert_eq!(
+        StationConfig::parse("SRS Player 124.8 neutrals"),
+        Some(StationConfig {
+            freq: Frequency::new(124_800_000, Modulation::AM),
+            coalition: Some(Coalition::Spectator),
+            path: None,
+        })
+    );
+}
+
+#[test]
+fn not_a_station_name() {
+    assert_eq!(StationConfig::parse("SRS Players"), None);
+    assert_eq!(StationConfig::parse("Tower SRS Player"), None);
+    assert_eq!(StationConfig::parse("Static Comm Tower"), None);
+}
+
+#[test]
+fn station_display_name() {
+    let config = StationConfig::parse("SRS Player 251 AM blue music/").unwrap();
+    assert_eq!(config.name(), "SRS Radio 251.000 AM");
+    let config = StationConfig::parse("SRS Player 30.5 FM").unwrap();
+    assert_eq!(config.name(), "SRS Radio 30.500 FM");
+}
+
+#[test]
+fn playlist_outside_of_base_path() {
+    assert_eq!(StationConfig::parse("SRS Player 251 AM /etc/passwd"), None);
+    assert_eq!(StationConfig::parse("SRS Player 251 AM ../music"), None);
+    assert_eq!(StationConfig::parse("SRS Player 251 AM music/../../atis.ogg"), None);
+    assert_eq!(
+        StationConfig::parse("SRS Player 251 AM ./music/atis.ogg").and_then(|c| c.path),
+        Some(PathBuf::from("./music/atis.ogg"))
+    );
+}
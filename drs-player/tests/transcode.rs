@@ -0,0 +1,168 @@
+#![cfg(feature = "transcode")]
+
+mod common;
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use drsplayer::{transcode, AudioFormat, Error};
+use ogg::reading::PacketReader;
+
+use crate::common::{opus_file, temp_path};
+
+/// Writes a WAV file containing one second of a 440 Hz sine tone.
+fn wav_file(name: &str, sample_rate: u32, channels: u16) -> PathBuf {
+    let path = temp_path(name);
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..sample_rate {
+        let t = f64::from(i) / f64::from(sample_rate);
+        let sample = ((t * 440.0 * 2.0 * std::f64::consts::PI).sin() * 16_000.0) as i16;
+        for _ in 0..channels {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+
+    path
+}
+
+/// Returns the duration (in samples at 48 kHz) of the frames of an Opus packet, based on its TOC
+/// byte.
+fn frame_samples(packet: &[u8]) -> u32 {
+    let config = packet[0] >> 3;
+    match config {
+        0..=11 => [480, 960, 1920, 2880][usize::from(config % 4)],
+        12..=15 => [480, 960][usize::from(config % 2)],
+        _ => [120, 240, 480, 960][usize::from(config % 4)],
+    }
+}
+
+#[test]
+fn wav_to_opus() {
+    let src = wav_file("wav_to_opus.wav", 44_100, 2);
+    let dst = temp_path("wav_to_opus.ogg");
+    transcode(&src, &dst).unwrap();
+    assert_eq!(AudioFormat::detect(&dst).unwrap(), Some(AudioFormat::Opus));
+
+    let mut rd = PacketReader::new(File::open(&dst).unwrap());
+    let head = rd.read_packet().unwrap().unwrap();
+    assert_eq!(&head.data[..8], b"OpusHead");
+    assert_eq!(head.data[9], 1, "expected a mono stream");
+    assert_eq!(&head.data[12..16], &16_000u32.to_le_bytes());
+    let pre_skip = u64::from(u16::from_le_bytes([head.data[10], head.data[11]]));
+    let tags = rd.read_packet().unwrap().unwrap();
+    assert_eq!(&tags.data[..8], b"OpusTags");
+
+    let mut packets = 0;
+    let mut last_granule = 0;
+    while let Some(pck) = rd.read_packet().unwrap() {
+        // 20ms frames (single frame packets)
+        assert_eq!(frame_samples(&pck.data), 960);
+        assert_eq!(pck.data[0] & 0x03, 0);
+        packets += 1;
+        last_granule = pck.absgp_page();
+    }
+    assert_eq!(packets, 50);
+    // the padding of the last frame is stripped
+    let duration = last_granule - pre_skip;
+    assert!(duration > 47_990 && duration <= 48_000, "duration: {}", duration);
+
+    fs::remove_file(src).unwrap();
+    fs::remove_file(dst).unwrap();
+}
+
+#[test]
+fn reencode_stereo_opus() {
+    // a stereo Ogg/Opus file containing 50 silent 20ms CELT packets
+    let packet: &[u8] = &[0xfc, 0xff, 0xfe];
+    let src = opus_file("reencode_stereo_opus_src.ogg", 2, &[packet; 50], 960);
+
+    let dst = temp_path("reencode_stereo_opus_dst.ogg");
+    transcode(&src, &dst).unwrap();
+
+    let mut rd = PacketReader::new(File::open(&dst).unwrap());
+    let head = rd.read_packet().unwrap().unwrap();
+    assert_eq!(head.data[9], 1, "expected a mono stream");
+    let mut packets = 0;
+    while rd.read_packet().unwrap().is_some() {
+        packets += 1;
+    }
+    // the comment header and one packet per 20ms
+    assert_eq!(packets, 1 + 50);
+
+    fs::remove_file(src).unwrap();
+    fs::remove_file(dst).unwrap();
+}
+
+#[test]
+fn detect_formats() {
+    let wav = wav_file("detect_formats.wav", 16_000, 1);
+    assert_eq!(AudioFormat::detect(&wav).unwrap(), Some(AudioFormat::Wav));
+    assert_eq!(AudioFormat::detect("music.mp3").unwrap(), Some(AudioFormat::Mp3));
+    assert_eq!(AudioFormat::detect("music.FLAC").unwrap(), Some(AudioFormat::Flac));
+    assert_eq!(AudioFormat::detect("notes.txt").unwrap(), None);
+
+    // Ogg files are detected by their content
+    let ogg = temp_path("detect_formats.ogg");
+    fs::write(&ogg, b"OggS\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x01vorbis").unwrap();
+    assert_eq!(AudioFormat::detect(&ogg).unwrap(), Some(AudioFormat::Vorbis));
+
+    fs::remove_file(wav).unwrap();
+    fs::remove_file(ogg).unwrap();
+}
+
+#[test]
+fn unsupported_format() {
+    let src = temp_path("unsupported_format.txt");
+    fs::write(&src, b"not audio").unwrap();
+
+    match transcode(&src, temp_path("unsupported_format.ogg")) {
+        Err(Error::UnsupportedFormat(path)) => assert_eq!(path, src),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("expected transcoding a text file to fail"),
+    }
+
+    fs::remove_file(src).unwrap();
+}
+
+#[test]
+fn concurrent_transcoding() {
+    let src = wav_file("concurrent_transcoding.wav", 16_000, 1);
+    let dir = temp_path("concurrent_transcoding");
+    fs::create_dir_all(&dir).unwrap();
+    let dst = dir.join("concurrent_transcoding.ogg");
+
+    // e.g. two stations playing the same file that is not cached yet
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let (src, dst) = (src.clone(), dst.clone());
+            std::thread::spawn(move || transcode(&src, &dst))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap().unwrap();
+    }
+
+    // no temporary files are left behind
+    let files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files, vec![dst.clone()]);
+    let mut rd = PacketReader::new(File::open(&dst).unwrap());
+    let mut packets = 0;
+    while rd.read_packet().unwrap().is_some() {
+        packets += 1;
+    }
+    // the headers and one second of audio
+    assert_eq!(packets, 2 + 50);
+
+    fs::remove_file(src).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
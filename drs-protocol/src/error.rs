@@ -0,0 +1,85 @@
+use std::{error, fmt};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::error::Error),
+    InvalidPacket(&'static str),
+    PacketTooLarge(usize),
+    InvalidCoalition(String),
+    InvalidModulation(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::Error::*;
+
+        match self {
+            InvalidPacket(reason) => write!(f, "Error: Invalid voice packet: {}", reason)?,
+            PacketTooLarge(len) => write!(
+                f,
+                "Error: Voice packet of {} bytes exceeds the maximum packet length",
+                len
+            )?,
+            InvalidCoalition(s) => write!(
+                f,
+                "Error: Invalid coalition `{}` (expected blue, red or spectator)",
+                s
+            )?,
+            InvalidModulation(s) => write!(
+                f,
+                "Error: Invalid modulation `{}` (expected AM, FM or intercom)",
+                s
+            )?,
+            _ => write!(f, "Error: {}", self.summary())?,
+        }
+
+        let mut source = error::Error::source(self);
+        while let Some(err) = source {
+            write!(f, "  -> {}", err)?;
+            source = err.source();
+        }
+
+        Ok(())
+    }
+}
+
+impl Error {
+    /// A short description of the kind of error.
+    fn summary(&self) -> &'static str {
+        use self::Error::*;
+
+        match *self {
+            Io(_) => "IO error",
+            Json(_) => "Error serializing/deserializing JSON RPC message",
+            InvalidPacket(_) => "Invalid voice packet",
+            PacketTooLarge(_) => "Voice packet too large",
+            InvalidCoalition(_) => "Invalid coalition",
+            InvalidModulation(_) => "Invalid modulation",
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use self::Error::*;
+
+        match *self {
+            Io(ref err) => Some(err),
+            Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::error::Error> for Error {
+    fn from(err: serde_json::error::Error) -> Self {
+        Error::Json(err)
+    }
+}
@@ -0,0 +1,17 @@
+//! The SRS (DCS Simple Radio Standalone) wire protocol: the JSON sync messages exchanged on the sync
+//! connection, and the binary voice packets.
+
+#![warn(rust_2018_idioms)]
+
+#[macro_use]
+extern crate serde_derive;
+
+mod error;
+mod sync;
+mod voice;
+
+pub use crate::error::Error;
+pub use crate::sync::{
+    Client, Coalition, LatLngPosition, Message, Modulation, MsgType, Position, Radio, RadioInfo,
+};
+pub use crate::voice::{VoiceFrequency, VoicePacket, GUID_LENGTH};
@@ -0,0 +1,319 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// A position in DCS coordinates (`alt` in meters).
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f64,
+    #[serde(rename = "z")]
+    pub y: f64,
+    #[serde(rename = "y")]
+    pub alt: f64,
+}
+
+/// A position as latitude and longitude (in degrees) and altitude (in meters), which newer SRS
+/// versions use instead of DCS coordinates.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct LatLngPosition {
+    pub lat: f64,
+    pub lng: f64,
+    pub alt: f64,
+}
+
+/// The type of a sync message, which is sent as the number of the SRS `MessageType` it
+/// corresponds to (given in parentheses).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MsgType {
+    /// The client's position and radios changed (`0`).
+    Update,
+    /// Keeps the connection alive (`1`).
+    Ping,
+    /// Initial sync of a client, and the server's reply with all clients (`2`).
+    Sync,
+    /// The client's radios changed (`3`).
+    RadioUpdate,
+    /// The server settings changed (`4`).
+    ServerSettings,
+    /// A client disconnected (`5`).
+    ClientDisconnect,
+    /// The server does not support the client version (`6`).
+    VersionMismatch,
+    /// `7`
+    ExternalAwacsModePassword,
+    /// `8`
+    ExternalAwacsModeDisconnect,
+}
+
+/// The modulation of a radio or voice packet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Modulation {
+    AM = 0,
+    FM = 1,
+    Intercom = 2,
+    Disabled = 3,
+}
+
+impl FromStr for Modulation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "am" => Ok(Modulation::AM),
+            "fm" => Ok(Modulation::FM),
+            "intercom" => Ok(Modulation::Intercom),
+            _ => Err(Error::InvalidModulation(s.to_string())),
+        }
+    }
+}
+
+impl Modulation {
+    /// Converts the raw modulation value used on the wire into a `Modulation`.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Modulation::AM),
+            1 => Some(Modulation::FM),
+            2 => Some(Modulation::Intercom),
+            3 => Some(Modulation::Disabled),
+            _ => None,
+        }
+    }
+}
+
+/// The coalition of a client.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Coalition {
+    Spectator,
+    Red,
+    Blue,
+}
+
+// `#[default]` on enum variants requires a newer compiler than the nightly the workspace is built
+// with (see `#![feature(try_trait)]` in drs-player)
+#[allow(clippy::derivable_impls)]
+impl Default for Coalition {
+    fn default() -> Self {
+        Coalition::Spectator
+    }
+}
+
+impl FromStr for Coalition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blue" => Ok(Coalition::Blue),
+            "red" => Ok(Coalition::Red),
+            "spectator" | "neutral" | "neutrals" => Ok(Coalition::Spectator),
+            _ => Err(Error::InvalidCoalition(s.to_string())),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Radio<'a> {
+    pub enc: bool,
+    pub enc_key: u8,
+    pub enc_mode: u8,
+    pub freq_max: f64,       // 1.0,
+    pub freq_min: f64,       // 1.0,
+    pub freq: f64,           // 1.0,
+    pub modulation: u8,      // 3,
+    pub name: Cow<'a, str>,  // "No Radio",
+    pub sec_freq: f64,       // 0.0,
+    pub volume: f32,         // 1.0,
+    pub freq_mode: u8,       // 0,
+    pub vol_mode: u8,        // 0,
+    pub expansion: bool,     // false,
+    pub channel: i32,        // -1,
+    pub simul: bool,         // false
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RadioInfo<'a> {
+    pub name: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Position>,
+    pub ptt: bool,
+    pub radios: Vec<Radio<'a>>,
+    pub control: u8,
+    pub selected: usize,
+    pub unit: Cow<'a, str>,
+    pub unit_id: usize,
+    pub simultaneous_transmission: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Client<'a> {
+    pub client_guid: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub position: Position,
+    /// The position as latitude and longitude (SRS 1.9 and newer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_lng_position: Option<LatLngPosition>,
+    pub coalition: Coalition,
+    /// The seat of a multi-crew aircraft the client occupies (SRS 2.0 and newer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seat: Option<u32>,
+    /// Whether the server may record the transmissions of the client (SRS 2.0 and newer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_record: Option<bool>,
+    pub radio_info: Option<RadioInfo<'a>>,
+    // ClientChannelId
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Message<'a> {
+    pub client: Option<Client<'a>>,
+    pub msg_type: MsgType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clients: Option<Vec<Client<'a>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_settings: Option<HashMap<String, String>>,
+    #[serde(
+        rename = "ExternalAWACSModePassword",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external_awacs_mode_password: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub version: Cow<'a, str>,
+}
+
+impl<'a> Message<'a> {
+    /// Encodes the message into a JSON line, as expected by SRS.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut data = serde_json::to_vec(self)?;
+        data.push(b'\n');
+        Ok(data)
+    }
+
+    /// Decodes a JSON line received from SRS. The decoded message does not borrow from the
+    /// provided data.
+    pub fn decode(data: &[u8]) -> Result<Message<'static>, Error> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+impl ::serde::Serialize for MsgType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        // Serialize the enum as a u64.
+        serializer.serialize_u64(match *self {
+            MsgType::Update => 0,
+            MsgType::Ping => 1,
+            MsgType::Sync => 2,
+            MsgType::RadioUpdate => 3,
+            MsgType::ServerSettings => 4,
+            MsgType::ClientDisconnect => 5,
+            MsgType::VersionMismatch => 6,
+            MsgType::ExternalAwacsModePassword => 7,
+            MsgType::ExternalAwacsModeDisconnect => 8,
+        })
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for MsgType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = MsgType;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("positive integer")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<MsgType, E>
+            where
+                E: ::serde::de::Error,
+            {
+                // Rust does not come with a simple way of converting a
+                // number to an enum, so use a big `match`.
+                match value {
+                    0 => Ok(MsgType::Update),
+                    1 => Ok(MsgType::Ping),
+                    2 => Ok(MsgType::Sync),
+                    3 => Ok(MsgType::RadioUpdate),
+                    4 => Ok(MsgType::ServerSettings),
+                    5 => Ok(MsgType::ClientDisconnect),
+                    6 => Ok(MsgType::VersionMismatch),
+                    7 => Ok(MsgType::ExternalAwacsModePassword),
+                    8 => Ok(MsgType::ExternalAwacsModeDisconnect),
+                    _ => Err(E::custom(format!(
+                        "unknown {} value: {}",
+                        stringify!(MsgType),
+                        value
+                    ))),
+                }
+            }
+        }
+
+        // Deserialize the enum from a u64.
+        deserializer.deserialize_u64(Visitor)
+    }
+}
+
+impl ::serde::Serialize for Coalition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        // Serialize the enum as a u64.
+        serializer.serialize_u64(match *self {
+            Coalition::Spectator => 0,
+            Coalition::Red => 1,
+            Coalition::Blue => 2,
+        })
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for Coalition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = Coalition;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("positive integer")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Coalition, E>
+            where
+                E: ::serde::de::Error,
+            {
+                // Rust does not come with a simple way of converting a
+                // number to an enum, so use a big `match`.
+                match value {
+                    0 => Ok(Coalition::Spectator),
+                    1 => Ok(Coalition::Red),
+                    2 => Ok(Coalition::Blue),
+                    _ => Err(E::custom(format!(
+                        "unknown {} value: {}",
+                        stringify!(Coalition),
+                        value
+                    ))),
+                }
+            }
+        }
+
+        // Deserialize the enum from a u64.
+        deserializer.deserialize_u64(Visitor)
+    }
+}
@@ -0,0 +1,182 @@
+use std::borrow::Cow;
+use std::io::{Cursor, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::Error;
+
+/// The length of the GUID identifying a SRS client.
+pub const GUID_LENGTH: usize = 22;
+
+/// Packet length, audio part length and frequency part length (each `u16`).
+const HEADER_SEGMENT_LENGTH: usize = 6;
+/// Frequency (`f64`), modulation (`u8`) and encryption (`u8`).
+const FREQUENCY_SEGMENT_LENGTH: usize = 10;
+/// Unit id (`u32`), packet id (`u64`) and GUID.
+const FIXED_SEGMENT_LENGTH: usize = 4 + 8 + GUID_LENGTH;
+
+/// A SRS voice packet, which consists of the following segments:
+///
+/// - header segment: the lengths of the packet, the audio part and the frequency part
+/// - audio segment: a single Opus packet
+/// - frequency segments: one for each frequency the audio is sent on
+/// - fixed segment: unit id, packet id and the GUID of the sending client
+#[derive(Debug, PartialEq, Clone)]
+pub struct VoicePacket<'a> {
+    pub audio: Cow<'a, [u8]>,
+    pub frequencies: Vec<VoiceFrequency>,
+    pub unit_id: u32,
+    pub packet_id: u64,
+    pub guid: Cow<'a, str>,
+}
+
+/// A frequency segment of a voice packet.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VoiceFrequency {
+    /// The frequency in Hz
+    pub freq: f64,
+    /// The raw modulation value (see `Modulation`)
+    pub modulation: u8,
+    /// The encryption key, or 0 if not encrypted
+    pub encryption: u8,
+}
+
+impl<'a> VoicePacket<'a> {
+    /// The length of the largest Opus packet that fits into a voice packet sent on the given
+    /// number of frequencies (all lengths are encoded as `u16`).
+    pub fn max_audio_length(frequencies: usize) -> usize {
+        usize::from(u16::MAX).saturating_sub(
+            HEADER_SEGMENT_LENGTH + frequencies * FREQUENCY_SEGMENT_LENGTH + FIXED_SEGMENT_LENGTH,
+        )
+    }
+
+    /// Encodes the voice packet into its binary representation.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        if self.guid.len() != GUID_LENGTH {
+            return Err(Error::InvalidPacket("GUID must be 22 bytes long"));
+        }
+
+        let len_audio_part = self.audio.len();
+        let len_frequency = self.frequencies.len() * FREQUENCY_SEGMENT_LENGTH;
+        let len_packet =
+            HEADER_SEGMENT_LENGTH + len_audio_part + len_frequency + FIXED_SEGMENT_LENGTH;
+        if len_packet > usize::from(u16::MAX) {
+            return Err(Error::PacketTooLarge(len_packet));
+        }
+
+        let mut frame = Cursor::new(Vec::with_capacity(len_packet));
+
+        // - HEADER SEGMENT
+        // Packet Length
+        frame.write_u16::<LittleEndian>(len_packet as u16)?;
+        // AudioPart1 Length
+        frame.write_u16::<LittleEndian>(len_audio_part as u16)?;
+        // FrequencyPart Length
+        frame.write_u16::<LittleEndian>(len_frequency as u16)?;
+
+        // - AUDIO SEGMENT
+        // AudioPart1
+        frame.write_all(&self.audio)?;
+
+        // - FREQUENCY SEGMENTS
+        for freq in &self.frequencies {
+            // Frequency
+            frame.write_f64::<LittleEndian>(freq.freq)?;
+            // Modulation
+            frame.write_all(&[freq.modulation])?;
+            // Encryption
+            frame.write_all(&[freq.encryption])?;
+        }
+
+        // - FIXED SEGMENT
+        // UnitId
+        frame.write_u32::<LittleEndian>(self.unit_id)?;
+        // PacketId
+        frame.write_u64::<LittleEndian>(self.packet_id)?;
+        // GUID
+        frame.write_all(self.guid.as_bytes())?;
+
+        Ok(frame.into_inner())
+    }
+
+    /// Reads the packet length from the header of the (possibly incomplete) voice packet at the
+    /// start of `data`, which is used to split a stream of voice packets (e.g. received over TCP)
+    /// into individual packets. Returns `Ok(None)` if not enough data is available yet.
+    pub fn peek_length(data: &[u8]) -> Result<Option<usize>, Error> {
+        if data.len() < 2 {
+            return Ok(None);
+        }
+
+        let len_packet = usize::from(Cursor::new(data).read_u16::<LittleEndian>()?);
+        if len_packet < HEADER_SEGMENT_LENGTH + FIXED_SEGMENT_LENGTH {
+            return Err(Error::InvalidPacket("packet too short"));
+        }
+
+        Ok(Some(len_packet))
+    }
+
+    /// Decodes a single, complete voice packet. The decoded packet borrows the audio and GUID
+    /// from the provided data.
+    pub fn decode(data: &'a [u8]) -> Result<VoicePacket<'a>, Error> {
+        if data.len() < HEADER_SEGMENT_LENGTH + FIXED_SEGMENT_LENGTH {
+            return Err(Error::InvalidPacket("packet too short"));
+        }
+
+        let mut header = Cursor::new(&data[..HEADER_SEGMENT_LENGTH]);
+        let len_packet = usize::from(header.read_u16::<LittleEndian>()?);
+        let len_audio_part = usize::from(header.read_u16::<LittleEndian>()?);
+        let len_frequency = usize::from(header.read_u16::<LittleEndian>()?);
+
+        if len_packet != data.len() {
+            return Err(Error::InvalidPacket("packet length mismatch"));
+        }
+        if HEADER_SEGMENT_LENGTH + len_audio_part + len_frequency + FIXED_SEGMENT_LENGTH
+            != len_packet
+        {
+            return Err(Error::InvalidPacket("segment lengths do not match packet length"));
+        }
+        if len_frequency % FREQUENCY_SEGMENT_LENGTH != 0 {
+            return Err(Error::InvalidPacket("invalid frequency segment length"));
+        }
+
+        let audio_start = HEADER_SEGMENT_LENGTH;
+        let frequency_start = audio_start + len_audio_part;
+        let fixed_start = frequency_start + len_frequency;
+
+        let audio = &data[audio_start..frequency_start];
+
+        let mut frequencies = Vec::with_capacity(len_frequency / FREQUENCY_SEGMENT_LENGTH);
+        for segment in data[frequency_start..fixed_start].chunks(FREQUENCY_SEGMENT_LENGTH) {
+            frequencies.push(VoiceFrequency {
+                freq: Cursor::new(&segment[..8]).read_f64::<LittleEndian>()?,
+                modulation: segment[8],
+                encryption: segment[9],
+            });
+        }
+
+        let mut fixed = Cursor::new(&data[fixed_start..]);
+        let unit_id = fixed.read_u32::<LittleEndian>()?;
+        let packet_id = fixed.read_u64::<LittleEndian>()?;
+        let guid = std::str::from_utf8(&data[fixed_start + 12..])
+            .map_err(|_| Error::InvalidPacket("GUID is not valid UTF-8"))?;
+
+        Ok(VoicePacket {
+            audio: Cow::Borrowed(audio),
+            frequencies,
+            unit_id,
+            packet_id,
+            guid: Cow::Borrowed(guid),
+        })
+    }
+
+    /// Returns a packet that does not borrow any data anymore.
+    pub fn into_owned(self) -> VoicePacket<'static> {
+        VoicePacket {
+            audio: Cow::Owned(self.audio.into_owned()),
+            frequencies: self.frequencies,
+            unit_id: self.unit_id,
+            packet_id: self.packet_id,
+            guid: Cow::Owned(self.guid.into_owned()),
+        }
+    }
+}
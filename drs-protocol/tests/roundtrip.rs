@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+
+use drs_protocol::{
+    Client, Coalition, LatLngPosition, Message, Modulation, MsgType, Position, Radio, RadioInfo,
+    VoiceFrequency, VoicePacket,
+};
+
+// The fixtures are hand-written after the format of the messages SRS 1.6 sends, they are not
+// captured from a real SRS server (see fixtures/README.md for how to add captured ones).
+const SYNC_REPLY: &[u8] = include_bytes!("fixtures/handmade_sync_reply.json");
+const CLIENT_DISCONNECT: &[u8] = include_bytes!("fixtures/handmade_client_disconnect.json");
+
+/// A (hand-written) voice packet containing a single 20ms Opus packet sent on 251 MHz AM (unencrypted).
+const VOICE_PACKET: &[u8] = &[
+    0x3a, 0x00, 0x08, 0x00, 0x0a, 0x00, 0x48, 0x0b, 0xe4, 0xc1, 0x36, 0xec,
+    0xc5, 0x80, 0x00, 0x00, 0x00, 0x80, 0xe9, 0xeb, 0xad, 0x41, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x52, 0x6a, 0x32, 0x72, 0x59, 0x76, 0x43, 0x30, 0x51, 0x46, 0x32, 0x72,
+    0x74, 0x30, 0x58, 0x65, 0x4a, 0x33, 0x53, 0x34, 0x66, 0x41,
+];
+
+#[test]
+fn decode_sync_reply() {
+    let msg = Message::decode(SYNC_REPLY).unwrap();
+    assert_eq!(msg.msg_type, MsgType::Sync);
+    assert_eq!(msg.version, "1.6.0.0");
+    assert!(msg.client.is_none());
+
+    let settings = msg.server_settings.as_ref().unwrap();
+    assert_eq!(settings["COALITION_AUDIO_SECURITY"], "False");
+
+    let clients = msg.clients.as_ref().unwrap();
+    assert_eq!(clients.len(), 2);
+
+    let viper = &clients[0];
+    assert_eq!(viper.client_guid, "Rj2rYvC0QF2rt0XeJ3S4fA");
+    assert_eq!(viper.coalition, Coalition::Blue);
+    assert_eq!(
+        viper.position,
+        Position {
+            x: -281702.5,
+            y: 647235.25,
+            alt: 1200.0,
+        }
+    );
+    let radios = &viper.radio_info.as_ref().unwrap().radios;
+    assert_eq!(radios.len(), 2);
+    assert_eq!(radios[1].freq, 251_000_000.0);
+    assert_eq!(radios[1].sec_freq, 243_000_000.0);
+    assert_eq!(Modulation::from_u8(radios[1].modulation), Some(Modulation::AM));
+
+    let spectator = &clients[1];
+    assert_eq!(spectator.coalition, Coalition::Spectator);
+    assert!(spectator.radio_info.is_none());
+}
+
+#[test]
+fn decode_client_disconnect() {
+    let msg = Message::decode(CLIENT_DISCONNECT).unwrap();
+    assert_eq!(msg.msg_type, MsgType::ClientDisconnect);
+    assert_eq!(msg.client.unwrap().client_guid, "t5kTr0bGRKuEGnAmJx5o3w");
+}
+
+#[test]
+fn sync_reply_round_trip() {
+    let msg = Message::decode(SYNC_REPLY).unwrap();
+    let encoded = msg.encode().unwrap();
+    assert_eq!(encoded.last(), Some(&b'\n'));
+    assert_eq!(Message::decode(&encoded).unwrap(), msg);
+}
+
+#[test]
+fn sync_message_round_trip() {
+    let msg = Message {
+        client: Some(Client {
+            client_guid: Cow::Borrowed("Rj2rYvC0QF2rt0XeJ3S4fA"),
+            name: Cow::Borrowed("ATIS Batumi"),
+            position: Position {
+                x: -356437.0,
+                y: 618211.0,
+                alt: 10.0,
+            },
+            coalition: Coalition::Red,
+            radio_info: Some(RadioInfo {
+                name: Cow::Borrowed("ATIS"),
+                pos: None,
+                radios: vec![Radio {
+                    enc: true,
+                    enc_key: 42,
+                    enc_mode: 2,
+                    freq: 131_500_000.0,
+                    modulation: Modulation::FM as u8,
+                    name: Cow::Borrowed("ATIS"),
+                    ..Default::default()
+                }],
+                unit: Cow::Borrowed("ATIS Batumi"),
+                simultaneous_transmission: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        msg_type: MsgType::Sync,
+        clients: None,
+        server_settings: None,
+        external_awacs_mode_password: None,
+        version: Cow::Borrowed("1.6.0.0"),
+    };
+
+    let encoded = msg.encode().unwrap();
+    let json = String::from_utf8(encoded.clone()).unwrap();
+    assert!(json.contains(r#""MsgType":2"#));
+    assert!(json.contains(r#""Coalition":1"#));
+    assert!(json.contains(r#""encKey":42"#));
+    // optional fields that are not set are not sent at all
+    assert!(!json.contains("Clients"));
+    assert!(!json.contains(r#""pos""#));
+    assert!(!json.contains("LatLngPosition"));
+    assert!(!json.contains("Seat"));
+    assert!(!json.contains("AllowRecord"));
+
+    assert_eq!(Message::decode(&encoded).unwrap(), msg);
+}
+
+#[test]
+fn msg_type_numbers() {
+    // the numbers of SRS's `MessageType`; changing them changes what is sent over the wire
+    let types = [
+        (MsgType::Update, 0),
+        (MsgType::Ping, 1),
+        (MsgType::Sync, 2),
+        (MsgType::RadioUpdate, 3),
+        (MsgType::ServerSettings, 4),
+        (MsgType::ClientDisconnect, 5),
+        (MsgType::VersionMismatch, 6),
+        (MsgType::ExternalAwacsModePassword, 7),
+        (MsgType::ExternalAwacsModeDisconnect, 8),
+    ];
+    for &(msg_type, number) in &types {
+        let msg = Message {
+            client: None,
+            msg_type,
+            clients: None,
+            server_settings: None,
+            external_awacs_mode_password: None,
+            version: Cow::Borrowed("1.6.0.0"),
+        };
+        let encoded = msg.encode().unwrap();
+        let json = String::from_utf8(encoded.clone()).unwrap();
+        assert!(json.contains(&format!(r#""MsgType":{}"#, number)), "{}", json);
+        assert_eq!(Message::decode(&encoded).unwrap().msg_type, msg_type);
+    }
+}
+
+#[test]
+fn client_fields_of_newer_versions() {
+    let client = Client {
+        client_guid: Cow::Borrowed("Rj2rYvC0QF2rt0XeJ3S4fA"),
+        name: Cow::Borrowed("ATIS Batumi"),
+        lat_lng_position: Some(LatLngPosition {
+            lat: 41.6,
+            lng: 41.6,
+            alt: 10.0,
+        }),
+        seat: Some(0),
+        allow_record: Some(true),
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&client).unwrap();
+    assert!(json.contains(r#""LatLngPosition":{"lat":41.6,"lng":41.6,"alt":10.0}"#));
+    assert!(json.contains(r#""Seat":0"#));
+    assert!(json.contains(r#""AllowRecord":true"#));
+    assert_eq!(serde_json::from_str::<Client<'_>>(&json).unwrap(), client);
+}
+
+#[test]
+fn decode_voice_packet() {
+    let pck = VoicePacket::decode(VOICE_PACKET).unwrap();
+    assert_eq!(
+        pck.audio.as_ref(),
+        &[0x48, 0x0b, 0xe4, 0xc1, 0x36, 0xec, 0xc5, 0x80][..]
+    );
+    assert_eq!(
+        pck.frequencies,
+        vec![VoiceFrequency {
+            freq: 251_000_000.0,
+            modulation: 0,
+            encryption: 0,
+        }]
+    );
+    assert_eq!(pck.unit_id, 0);
+    assert_eq!(pck.packet_id, 42);
+    assert_eq!(pck.guid, "Rj2rYvC0QF2rt0XeJ3S4fA");
+}
+
+#[test]
+fn voice_packet_round_trip() {
+    let pck = VoicePacket::decode(VOICE_PACKET).unwrap();
+    assert_eq!(pck.encode().unwrap(), VOICE_PACKET);
+}
+
+#[test]
+fn voice_packet_multiple_frequencies_round_trip() {
+    let pck = VoicePacket {
+        audio: Cow::Borrowed(&[0xfc, 0xff, 0xfe][..]),
+        frequencies: vec![
+            VoiceFrequency {
+                freq: 243_000_000.0,
+                modulation: Modulation::AM as u8,
+                encryption: 0,
+            },
+            VoiceFrequency {
+                freq: 30_000_000.0,
+                modulation: Modulation::FM as u8,
+                encryption: 7,
+            },
+        ],
+        unit_id: 16_777_472,
+        packet_id: u64::MAX,
+        guid: Cow::Borrowed("t5kTr0bGRKuEGnAmJx5o3w"),
+    };
+
+    let encoded = pck.encode().unwrap();
+    assert_eq!(encoded.len(), 6 + 3 + 2 * 10 + 4 + 8 + 22);
+    assert_eq!(VoicePacket::decode(&encoded).unwrap(), pck);
+}
+
+#[test]
+fn decode_invalid_voice_packets() {
+    // too short
+    assert!(VoicePacket::decode(&VOICE_PACKET[..20]).is_err());
+    // truncated (packet length does not match)
+    assert!(VoicePacket::decode(&VOICE_PACKET[..VOICE_PACKET.len() - 1]).is_err());
+
+    // segment lengths do not add up
+    let mut invalid = VOICE_PACKET.to_vec();
+    invalid[2] = 0x09;
+    assert!(VoicePacket::decode(&invalid).is_err());
+}
+
+#[test]
+fn split_voice_packet_stream() {
+    let mut stream = VOICE_PACKET.to_vec();
+    stream.extend_from_slice(&VOICE_PACKET[..10]);
+
+    assert_eq!(VoicePacket::peek_length(&stream[..1]).unwrap(), None);
+    let len = VoicePacket::peek_length(&stream).unwrap().unwrap();
+    assert_eq!(len, VOICE_PACKET.len());
+    assert!(VoicePacket::decode(&stream[..len]).is_ok());
+    assert_eq!(VoicePacket::peek_length(&stream[len..]).unwrap(), Some(len));
+
+    // a length that cannot even hold the header and fixed segment
+    assert!(VoicePacket::peek_length(&[0x05, 0x00]).is_err());
+}
+
+#[test]
+fn encode_oversized_voice_packet() {
+    let audio = vec![0; 70_000];
+    let pck = VoicePacket {
+        audio: Cow::Borrowed(&audio),
+        frequencies: Vec::new(),
+        unit_id: 0,
+        packet_id: 1,
+        guid: Cow::Borrowed("Rj2rYvC0QF2rt0XeJ3S4fA"),
+    };
+    assert!(pck.encode().is_err());
+}
+
+#[test]
+fn encode_largest_voice_packet() {
+    let frequencies = vec![
+        VoiceFrequency {
+            freq: 251_000_000.0,
+            modulation: 0,
+            encryption: 0,
+        };
+        2
+    ];
+    let max = VoicePacket::max_audio_length(frequencies.len());
+    let audio = vec![0; max + 1];
+    let mut pck = VoicePacket {
+        audio: Cow::Borrowed(&audio[..max]),
+        frequencies,
+        unit_id: 0,
+        packet_id: 1,
+        guid: Cow::Borrowed("Rj2rYvC0QF2rt0XeJ3S4fA"),
+    };
+    assert_eq!(pck.encode().unwrap().len(), usize::from(u16::MAX));
+
+    pck.audio = Cow::Borrowed(&audio);
+    assert!(pck.encode().is_err());
+}
@@ -0,0 +1,495 @@
+//! A minimal SRS server for testing SRS clients (like `drsplayer`) without a running SRS server.
+//! It accepts sync and voice (TCP and UDP) connections, records all received sync messages and
+//! voice packets, and can simulate disconnects and slow peers.
+
+#![warn(rust_2018_idioms)]
+
+#[macro_use]
+extern crate log;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use drs_protocol::{Client, Message, MsgType, VoicePacket, GUID_LENGTH};
+
+/// A running mock SRS server, which is shut down once dropped.
+pub struct MockServer {
+    port: u16,
+    tcp_voice_port: u16,
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    stopped: AtomicBool,
+    udp: UdpSocket,
+    state: Mutex<State>,
+}
+
+struct State {
+    version: String,
+    /// The oldest client version the server accepts.
+    min_client_version: Option<String>,
+    settings: HashMap<String, String>,
+    delay: Duration,
+    /// Raw data sent right before each sync reply.
+    sync_reply_prefix: Vec<u8>,
+    /// All clients that are known to the server (connected or added through `add_client`).
+    clients: Vec<Client<'static>>,
+    messages: Vec<Message<'static>>,
+    voice_packets: Vec<VoicePacket<'static>>,
+    udp_pings: usize,
+    next_conn_id: usize,
+    sync_streams: Vec<(usize, TcpStream)>,
+    voice_streams: Vec<(usize, TcpStream)>,
+    udp_peers: Vec<SocketAddr>,
+}
+
+impl MockServer {
+    /// Starts a server on localhost, listening on arbitrary free ports.
+    pub fn start() -> Result<Self, io::Error> {
+        MockServer::bind(0)
+    }
+
+    /// Starts a server on localhost, listening for sync connections and UDP voice packets on the
+    /// given port and for TCP voice connections on the following port (like SRS does). If the port
+    /// is `0`, arbitrary free ports are used. Fails for port 65535, which has no following port.
+    pub fn bind(port: u16) -> Result<Self, io::Error> {
+        let voice_listener = if port == 0 {
+            TcpListener::bind(("127.0.0.1", 0))?
+        } else {
+            let voice_port = port.checked_add(1).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "no port follows port 65535")
+            })?;
+            TcpListener::bind(("127.0.0.1", voice_port))?
+        };
+        let sync_listener = TcpListener::bind(("127.0.0.1", port))?;
+        let port = sync_listener.local_addr()?.port();
+        let udp = UdpSocket::bind(("127.0.0.1", port))?;
+        let tcp_voice_port = voice_listener.local_addr()?.port();
+
+        sync_listener.set_nonblocking(true)?;
+        voice_listener.set_nonblocking(true)?;
+        udp.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+        let shared = Arc::new(Shared {
+            stopped: AtomicBool::new(false),
+            udp,
+            state: Mutex::new(State {
+                version: "1.6.0.0".to_string(),
+                min_client_version: None,
+                settings: HashMap::new(),
+                delay: Duration::from_secs(0),
+                sync_reply_prefix: Vec::new(),
+                clients: Vec::new(),
+                messages: Vec::new(),
+                voice_packets: Vec::new(),
+                udp_pings: 0,
+                next_conn_id: 0,
+                sync_streams: Vec::new(),
+                voice_streams: Vec::new(),
+                udp_peers: Vec::new(),
+            }),
+        });
+
+        let mut threads = Vec::new();
+        let s = shared.clone();
+        threads.push(thread::spawn(move || accept(sync_listener, s, handle_sync)));
+        let s = shared.clone();
+        threads.push(thread::spawn(move || accept(voice_listener, s, handle_tcp_voice)));
+        let s = shared.clone();
+        threads.push(thread::spawn(move || handle_udp_voice(s)));
+
+        Ok(MockServer {
+            port,
+            tcp_voice_port,
+            shared,
+            threads,
+        })
+    }
+
+    /// The port used for sync connections and UDP voice packets.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The port used for TCP voice connections.
+    pub fn tcp_voice_port(&self) -> u16 {
+        self.tcp_voice_port
+    }
+
+    /// Sets the SRS version the server reports (defaults to `1.6.0.0`).
+    pub fn set_version(&self, version: &str) {
+        self.shared.state.lock().unwrap().version = version.to_string();
+    }
+
+    /// Sets the oldest client version the server accepts (all versions by default). Like SRS
+    /// servers (which compare the client version with their minimum protocol version), the server
+    /// replies to the sync message of older clients with a version mismatch message and closes the
+    /// connection.
+    pub fn set_min_client_version(&self, version: &str) {
+        self.shared.state.lock().unwrap().min_client_version = Some(version.to_string());
+    }
+
+    /// Sets a server setting that is sent to clients with the sync reply.
+    pub fn set_setting(&self, key: &str, value: &str) {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .settings
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Simulates a slow peer by waiting the given duration before handling each received sync
+    /// message and voice packet.
+    pub fn set_delay(&self, delay: Duration) {
+        self.shared.state.lock().unwrap().delay = delay;
+    }
+
+    /// Sets raw data that is sent right before each sync reply, e.g. to simulate messages the
+    /// client cannot decode.
+    pub fn set_sync_reply_prefix(&self, data: &[u8]) {
+        self.shared.state.lock().unwrap().sync_reply_prefix = data.to_vec();
+    }
+
+    /// Adds (or updates) a client that is not actually connected, and notifies all connected
+    /// clients about it.
+    pub fn add_client(&self, client: Client<'static>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.update_client(&client);
+
+        let msg = Message {
+            client: Some(client),
+            msg_type: MsgType::RadioUpdate,
+            clients: None,
+            server_settings: None,
+            external_awacs_mode_password: None,
+            version: Cow::Owned(state.version.clone()),
+        };
+        if let Ok(data) = msg.encode() {
+            for (_, stream) in &mut state.sync_streams {
+                let _ = stream.write_all(&data);
+            }
+        }
+    }
+
+    /// Returns all sync messages received so far.
+    pub fn messages(&self) -> Vec<Message<'static>> {
+        self.shared.state.lock().unwrap().messages.clone()
+    }
+
+    /// Returns all voice packets received so far (via TCP and UDP).
+    pub fn voice_packets(&self) -> Vec<VoicePacket<'static>> {
+        self.shared.state.lock().unwrap().voice_packets.clone()
+    }
+
+    /// Returns the number of UDP pings received so far.
+    pub fn udp_pings(&self) -> usize {
+        self.shared.state.lock().unwrap().udp_pings
+    }
+
+    /// Returns the addresses UDP voice packets and pings were received from so far.
+    pub fn udp_peers(&self) -> Vec<SocketAddr> {
+        self.shared.state.lock().unwrap().udp_peers.clone()
+    }
+
+    /// Sends the given voice packet to all connected voice clients.
+    pub fn send_voice(&self, pck: &VoicePacket<'_>) -> Result<(), io::Error> {
+        let data = pck
+            .encode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+        let mut state = self.shared.state.lock().unwrap();
+        for (_, stream) in &mut state.voice_streams {
+            stream.write_all(&data)?;
+        }
+        for addr in &state.udp_peers {
+            self.shared.udp.send_to(&data, addr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes all sync and TCP voice connections and forgets all UDP peers, as if the server was
+    /// restarted.
+    pub fn disconnect_all(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        let state = &mut *state;
+        for (_, stream) in state.sync_streams.drain(..).chain(state.voice_streams.drain(..)) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        state.udp_peers.clear();
+    }
+
+    /// Waits until the given condition is met. Returns `false` if it is not met within `timeout`.
+    pub fn wait_for<F>(&self, timeout: Duration, condition: F) -> bool
+    where
+        F: Fn(&MockServer) -> bool,
+    {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if condition(self) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        condition(self)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.disconnect_all();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl State {
+    fn update_client(&mut self, client: &Client<'static>) {
+        match self
+            .clients
+            .iter_mut()
+            .find(|c| c.client_guid == client.client_guid)
+        {
+            Some(existing) => {
+                let radio_info = existing.radio_info.take();
+                *existing = client.clone();
+                // metadata updates do not contain radio information
+                if existing.radio_info.is_none() {
+                    existing.radio_info = radio_info;
+                }
+            }
+            None => self.clients.push(client.clone()),
+        }
+    }
+}
+
+fn accept(listener: TcpListener, shared: Arc<Shared>, handler: fn(TcpStream, usize, Arc<Shared>)) {
+    while !shared.stopped.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                debug!("Accepted connection from {}", addr);
+                if let Err(err) = stream.set_nonblocking(false) {
+                    error!("Error accepting connection: {}", err);
+                    continue;
+                }
+
+                let id = {
+                    let mut state = shared.state.lock().unwrap();
+                    state.next_conn_id += 1;
+                    state.next_conn_id
+                };
+                let shared = shared.clone();
+                thread::spawn(move || handler(stream, id, shared));
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(err) => {
+                error!("Error accepting connection: {}", err);
+                return;
+            }
+        }
+    }
+}
+
+fn handle_sync(stream: TcpStream, id: usize, shared: Arc<Shared>) {
+    let (mut rd, mut wr) = match (stream.try_clone(), stream.try_clone()) {
+        (Ok(rd), Ok(wr)) => (BufReader::new(rd), wr),
+        _ => return,
+    };
+    shared.state.lock().unwrap().sync_streams.push((id, stream));
+
+    let mut data = Vec::new();
+    loop {
+        data.clear();
+        match rd.read_until(b'\n', &mut data) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let delay = shared.state.lock().unwrap().delay;
+        thread::sleep(delay);
+
+        let msg = match Message::decode(&data) {
+            Ok(msg) => msg,
+            Err(err) => {
+                warn!("Received invalid sync message: {}", err);
+                continue;
+            }
+        };
+        debug!("Received {:?} message", msg.msg_type);
+
+        let reply = {
+            let mut state = shared.state.lock().unwrap();
+            let guid = msg.client.as_ref().map(|c| c.client_guid.clone());
+            if let Some(ref client) = msg.client {
+                state.update_client(client);
+            }
+
+            let outdated = state
+                .min_client_version
+                .as_ref()
+                .map(|min| parse_version(&msg.version) < parse_version(min))
+                .unwrap_or(false);
+            let reply = if msg.msg_type == MsgType::Sync && outdated {
+                Some(Message {
+                    client: None,
+                    msg_type: MsgType::VersionMismatch,
+                    clients: None,
+                    server_settings: None,
+                    external_awacs_mode_password: None,
+                    version: Cow::Owned(state.version.clone()),
+                })
+            } else if msg.msg_type == MsgType::Sync {
+                Some(Message {
+                    client: None,
+                    msg_type: MsgType::Sync,
+                    clients: Some(
+                        state
+                            .clients
+                            .iter()
+                            .filter(|c| Some(&c.client_guid) != guid.as_ref())
+                            .cloned()
+                            .collect(),
+                    ),
+                    server_settings: Some(state.settings.clone()),
+                    external_awacs_mode_password: None,
+                    version: Cow::Owned(state.version.clone()),
+                })
+            } else {
+                None
+            };
+            state.messages.push(msg);
+            reply.map(|reply| (state.sync_reply_prefix.clone(), reply))
+        };
+
+        if let Some((prefix, reply)) = reply {
+            let sent = reply
+                .encode()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+                .and_then(|data| wr.write_all(&[prefix, data].concat()));
+            if let Err(err) = sent {
+                warn!("Error sending sync reply: {}", err);
+                break;
+            }
+            if reply.msg_type == MsgType::VersionMismatch {
+                let _ = wr.shutdown(Shutdown::Both);
+                break;
+            }
+        }
+    }
+
+    debug!("Sync connection closed");
+    shared
+        .state
+        .lock()
+        .unwrap()
+        .sync_streams
+        .retain(|(i, _)| *i != id);
+}
+
+/// Parses a version like `1.9.0.0` into its parts for comparison (unparsable parts count as 0).
+fn parse_version(version: &str) -> Vec<u16> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+fn handle_tcp_voice(stream: TcpStream, id: usize, shared: Arc<Shared>) {
+    let mut rd = match stream.try_clone() {
+        Ok(rd) => rd,
+        Err(_) => return,
+    };
+    shared.state.lock().unwrap().voice_streams.push((id, stream));
+
+    loop {
+        let mut data = vec![0; 2];
+        if rd.read_exact(&mut data).is_err() {
+            break;
+        }
+        let len = match VoicePacket::peek_length(&data) {
+            Ok(Some(len)) => len,
+            _ => {
+                warn!("Received invalid voice packet length");
+                break;
+            }
+        };
+        data.resize(len, 0);
+        if rd.read_exact(&mut data[2..]).is_err() {
+            break;
+        }
+
+        let delay = shared.state.lock().unwrap().delay;
+        thread::sleep(delay);
+
+        match VoicePacket::decode(&data) {
+            Ok(pck) => {
+                let pck = pck.into_owned();
+                shared.state.lock().unwrap().voice_packets.push(pck);
+            }
+            Err(err) => warn!("Received invalid voice packet: {}", err),
+        }
+    }
+
+    debug!("Voice connection closed");
+    shared
+        .state
+        .lock()
+        .unwrap()
+        .voice_streams
+        .retain(|(i, _)| *i != id);
+}
+
+fn handle_udp_voice(shared: Arc<Shared>) {
+    let mut buf = vec![0; 65_535];
+    while !shared.stopped.load(Ordering::SeqCst) {
+        let (len, addr) = match shared.udp.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(ref err)
+                if err.kind() == io::ErrorKind::TimedOut
+                    || err.kind() == io::ErrorKind::WouldBlock =>
+            {
+                continue
+            }
+            Err(err) => {
+                error!("Error receiving UDP voice packet: {}", err);
+                return;
+            }
+        };
+        let data = &buf[..len];
+
+        let delay = shared.state.lock().unwrap().delay;
+        thread::sleep(delay);
+
+        let mut state = shared.state.lock().unwrap();
+        if !state.udp_peers.contains(&addr) {
+            state.udp_peers.push(addr);
+        }
+
+        if data.len() == GUID_LENGTH {
+            // pings are echoed back (like SRS does)
+            state.udp_pings += 1;
+            if let Err(err) = shared.udp.send_to(data, addr) {
+                warn!("Error answering UDP ping: {}", err);
+            }
+            continue;
+        }
+
+        match VoicePacket::decode(data) {
+            Ok(pck) => state.voice_packets.push(pck.into_owned()),
+            Err(err) => warn!("Received invalid voice packet: {}", err),
+        }
+    }
+}
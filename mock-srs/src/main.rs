@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate log;
+
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use mock_srs::MockServer;
+
+pub fn main() {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Debug)
+        .try_init()
+        .unwrap();
+
+    let matches = clap::App::new("mock-srs")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(clap::Arg::with_name("port")
+            .short("p")
+            .long("port")
+            .default_value("5002")
+            .help("Sets the port for sync connections and UDP voice packets (TCP voice connections \
+                   are accepted on the following port)")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("srs_version")
+            .long("srs-version")
+            .default_value("1.6.0.0")
+            .help("Sets the SRS version the server reports")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("delay")
+            .long("delay")
+            .help("Simulates a slow peer by waiting the given number of milliseconds before \
+                   handling each received message and voice packet")
+            .takes_value(true))
+        .get_matches();
+
+    let port = if let Ok(port) = u16::from_str(matches.value_of("port").unwrap()) {
+        port
+    } else {
+        error!("The provided port is not a valid port number");
+        return;
+    };
+
+    let server = match MockServer::bind(port) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Error starting mock SRS server: {}", err);
+            return;
+        }
+    };
+    server.set_version(matches.value_of("srs_version").unwrap());
+    if let Some(delay) = matches.value_of("delay") {
+        if let Ok(delay) = u64::from_str(delay) {
+            server.set_delay(Duration::from_millis(delay));
+        } else {
+            error!("The provided delay is not a valid number");
+            return;
+        }
+    }
+
+    info!(
+        "Mock SRS server listening on port {} (TCP voice on port {})",
+        server.port(),
+        server.tcp_voice_port()
+    );
+
+    let mut received = 0;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        let count = server.voice_packets().len();
+        if count > received {
+            info!("Received {} voice packets", count - received);
+            received = count;
+        }
+    }
+}